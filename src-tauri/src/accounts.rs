@@ -0,0 +1,390 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use oauth2::{basic::BasicTokenResponse, TokenResponse};
+use serde::{Deserialize, Serialize};
+use tauri::{async_runtime::JoinHandle, AppHandle, Manager};
+
+use crate::{
+    config::Host,
+    credential_store::CredentialStore,
+    github::{GitHub, Repository, StreamHealth},
+    json_file,
+};
+
+const ACCOUNTS_FILE_NAME: &str = "accounts.json";
+// Keyring entry used before multiple accounts were supported
+const LEGACY_ACCOUNT_ID: &str = "user";
+// Expiring tokens are renewed this long before they stop working
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Token kept in the credential store, along with what's needed to renew expiring GitHub App user tokens
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Credentials {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+    /// Missing for credentials saved before personal access tokens were supported
+    #[serde(default)]
+    pub kind: CredentialsKind,
+}
+
+/// How the token was obtained
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialsKind {
+    /// Granted to the OAuth or GitHub App through the browser or a device code
+    #[default]
+    Grant,
+    PersonalAccessToken,
+}
+
+impl Credentials {
+    pub fn new(access_token: String, kind: CredentialsKind) -> Self {
+        Self {
+            access_token,
+            refresh_token: None,
+            expires_at: None,
+            kind,
+        }
+    }
+
+    pub fn from_token_response(token_response: &BasicTokenResponse) -> Self {
+        Self {
+            access_token: token_response.access_token().secret().to_owned(),
+            refresh_token: token_response
+                .refresh_token()
+                .map(|refresh_token| refresh_token.secret().to_owned()),
+            expires_at: token_response
+                .expires_in()
+                .map(|expires_in| SystemTime::now() + expires_in),
+            kind: CredentialsKind::Grant,
+        }
+    }
+
+    /// Time left until the token should be refreshed, `None` for tokens that can't be refreshed
+    pub fn refresh_in(&self) -> Option<Duration> {
+        self.refresh_token.as_ref()?;
+
+        Some(
+            self.expires_at?
+                .checked_sub(REFRESH_MARGIN)?
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Account {
+    /// Name of the credential store entry holding the account's token
+    pub id: String,
+    pub host: Host,
+    pub login: Option<String>,
+}
+
+impl Account {
+    pub fn new(login: String, host: Host) -> Self {
+        Self {
+            id: format!("{}@{}", login, host.web_url.host_str().unwrap_or_default()),
+            host,
+            login: Some(login),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.login.as_deref().unwrap_or(&self.id)
+    }
+}
+
+pub struct Accounts {
+    path: Option<PathBuf>,
+    accounts: Mutex<Vec<Account>>,
+    credential_store: Box<dyn CredentialStore>,
+}
+
+impl Accounts {
+    pub fn load(
+        app: &AppHandle,
+        default_host: &Host,
+        credential_store: Box<dyn CredentialStore>,
+    ) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(ACCOUNTS_FILE_NAME));
+        // Also when the file is corrupt, so at least the oldest account isn't lost
+        let accounts = path
+            .as_deref()
+            .and_then(json_file::load)
+            .unwrap_or_else(|| {
+                Self::legacy_account(credential_store.as_ref(), default_host)
+                    .into_iter()
+                    .collect()
+            });
+
+        Self {
+            path,
+            accounts: Mutex::new(accounts),
+            credential_store,
+        }
+    }
+
+    fn legacy_account(credential_store: &dyn CredentialStore, host: &Host) -> Option<Account> {
+        credential_store
+            .get(LEGACY_ACCOUNT_ID)
+            .ok()
+            .flatten()
+            .map(|_| Account {
+                id: LEGACY_ACCOUNT_ID.to_owned(),
+                host: host.clone(),
+                login: None,
+            })
+    }
+
+    pub fn credential_store_name(&self) -> &'static str {
+        self.credential_store.name()
+    }
+
+    pub fn credentials(&self, account: &Account) -> Option<Credentials> {
+        let secret = match self.credential_store.get(&account.id) {
+            Ok(secret) => secret?,
+            Err(error) => {
+                println!(
+                    "Failed to read credentials of {}: {:?}",
+                    account.label(),
+                    error
+                );
+                return None;
+            }
+        };
+
+        // Tokens used to be stored as plain strings, back when they could only come from OAuth
+        Some(
+            serde_json::from_str(&secret)
+                .unwrap_or_else(|_| Credentials::new(secret, CredentialsKind::Grant)),
+        )
+    }
+
+    pub fn list(&self) -> Vec<Account> {
+        self.accounts.lock().unwrap().clone()
+    }
+
+    /// Stores the account's credentials, replacing the account if it's already known
+    pub fn add(
+        &self,
+        account: Account,
+        credentials: &Credentials,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.credential_store
+            .set(&account.id, &serde_json::to_string(credentials)?)?;
+
+        {
+            let mut accounts = self.accounts.lock().unwrap();
+            match accounts.iter_mut().find(|existing| {
+                existing.id == account.id
+                    || (existing.login == account.login && existing.host == account.host)
+            }) {
+                Some(existing) => *existing = account,
+                None => accounts.push(account),
+            }
+        }
+
+        self.save()
+    }
+
+    /// Forgets the account and deletes its credentials. The account is forgotten even if
+    /// deleting the credentials fails, e.g. because the keyring is locked.
+    pub fn remove(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .retain(|account| account.id != id);
+        self.save()?;
+
+        self.credential_store.delete(id)
+    }
+
+    pub fn set_login(&self, id: &str, login: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(account) = self
+            .accounts
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|account| account.id == id)
+        {
+            account.login = Some(login.to_owned());
+        }
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Err("App data directory is not available".into());
+        };
+
+        json_file::save(path, &*self.accounts.lock().unwrap())
+    }
+}
+
+/// Number of unread notifications per account id, combined for the tray title
+#[derive(Default)]
+pub struct UnreadCounts(Mutex<HashMap<String, usize>>);
+
+impl UnreadCounts {
+    pub fn set(&self, account_id: &str, count: usize) -> usize {
+        let mut counts = self.0.lock().unwrap();
+        counts.insert(account_id.to_owned(), count);
+
+        counts.values().sum()
+    }
+
+    pub fn remove(&self, account_id: &str) -> usize {
+        let mut counts = self.0.lock().unwrap();
+        counts.remove(account_id);
+
+        counts.values().sum()
+    }
+}
+
+/// Health of each account's notifications stream, shown in the tray menu
+#[derive(Default)]
+pub struct StreamHealths(Mutex<HashMap<String, StreamHealth>>);
+
+impl StreamHealths {
+    /// Returns `true` if the health changed and the tray menu should be refreshed
+    pub fn set(&self, account_id: &str, health: StreamHealth) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(account_id.to_owned(), health.clone())
+            != Some(health)
+    }
+
+    pub fn get(&self, account_id: &str) -> StreamHealth {
+        self.0
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&self, account_id: &str) {
+        self.0.lock().unwrap().remove(account_id);
+    }
+}
+
+/// Signed in API clients per account id, for actions started outside the polling task
+#[derive(Default)]
+pub struct Clients(Mutex<HashMap<String, GitHub>>);
+
+impl Clients {
+    pub fn insert(&self, account_id: &str, github: GitHub) {
+        self.0.lock().unwrap().insert(account_id.to_owned(), github);
+    }
+
+    pub fn get(&self, account_id: &str) -> Option<GitHub> {
+        self.0.lock().unwrap().get(account_id).cloned()
+    }
+
+    pub fn remove(&self, account_id: &str) {
+        self.0.lock().unwrap().remove(account_id);
+    }
+}
+
+/// Repositories each account is watching, listed in the tray menu
+#[derive(Default)]
+pub struct WatchedRepositories {
+    repositories: Mutex<HashMap<String, Vec<Repository>>>,
+    /// Full names of repositories that had notifications, most recent first
+    recent: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl WatchedRepositories {
+    pub fn set(&self, account_id: &str, repositories: Vec<Repository>) {
+        self.repositories
+            .lock()
+            .unwrap()
+            .insert(account_id.to_owned(), repositories);
+    }
+
+    pub fn get(&self, account_id: &str) -> Vec<Repository> {
+        self.repositories
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Up to `limit` watched repositories, the ones that recently had notifications first
+    pub fn most_active(&self, account_id: &str, limit: usize) -> Vec<Repository> {
+        let recent = self
+            .recent
+            .lock()
+            .unwrap()
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default();
+        let mut repositories = self.get(account_id);
+        // Stable, so repositories without notifications keep the order GitHub listed them in
+        repositories.sort_by_key(|repository| {
+            recent
+                .iter()
+                .position(|full_name| *full_name == repository.full_name)
+                .unwrap_or(usize::MAX)
+        });
+        repositories.truncate(limit);
+
+        repositories
+    }
+
+    /// Moves the repositories to the front of the recently active ones
+    pub fn note_activity<'a>(&self, account_id: &str, full_names: impl Iterator<Item = &'a str>) {
+        let mut recent = self.recent.lock().unwrap();
+        let recent = recent.entry(account_id.to_owned()).or_default();
+
+        for full_name in full_names {
+            recent.retain(|existing| existing != full_name);
+            recent.insert(0, full_name.to_owned());
+        }
+    }
+
+    /// Stops listing a single repository, e.g. after it was unwatched
+    pub fn remove_repository(&self, account_id: &str, full_name: &str) {
+        if let Some(repositories) = self.repositories.lock().unwrap().get_mut(account_id) {
+            repositories.retain(|repository| repository.full_name != full_name);
+        }
+    }
+
+    pub fn remove(&self, account_id: &str) {
+        self.repositories.lock().unwrap().remove(account_id);
+        self.recent.lock().unwrap().remove(account_id);
+    }
+}
+
+/// Running notification polling tasks per account id
+#[derive(Default)]
+pub struct MonitoringTasks(Mutex<HashMap<String, JoinHandle<()>>>);
+
+impl MonitoringTasks {
+    /// Tracks the task, stopping the one previously running for the same account
+    pub fn insert(&self, account_id: &str, task: JoinHandle<()>) {
+        if let Some(previous_task) = self.0.lock().unwrap().insert(account_id.to_owned(), task) {
+            previous_task.abort();
+        }
+    }
+
+    pub fn abort(&self, account_id: &str) {
+        if let Some(task) = self.0.lock().unwrap().remove(account_id) {
+            task.abort();
+        }
+    }
+}
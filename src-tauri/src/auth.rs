@@ -1,29 +1,232 @@
-use std::time::Duration;
+use std::{fmt, net::Ipv4Addr, pin::pin, time::Duration};
 
+use futures::future::{self, Either};
 use oauth2::{
-    basic::{BasicClient, BasicTokenType},
+    basic::{BasicClient, BasicErrorResponse, BasicTokenResponse, BasicTokenType},
     AuthUrl, ClientId, ClientSecret, CsrfToken, EmptyExtraTokenFields, PkceCodeChallenge,
-    RedirectUrl, Scope, StandardTokenResponse, TokenUrl,
+    RedirectUrl, RefreshToken, RequestTokenError, Scope, StandardTokenResponse, TokenUrl,
 };
+use serde::Deserialize;
 use tauri::{Listener, Url};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tauri_plugin_http::reqwest;
 use tauri_plugin_opener::OpenerExt;
-use tokio::sync::oneshot;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+};
+
+use crate::{
+    config::{Host, NetworkConfig, NetworkError},
+    constants::{AuthRedirectEventPayload, AUTH_REDIRECT_EVENT},
+    github::GitHubError,
+};
+
+const AUTH_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+/// How long a browser connection may stay idle before its request line arrives
+const LOOPBACK_READ_TIMEOUT: Duration = Duration::from_secs(10);
+const LOOPBACK_SUCCESS_PAGE: &str = "<!doctype html><html><head><title>GitHub Notifier</title></head><body><p>You're signed in. You can close this tab and return to GitHub Notifier.</p></body></html>";
+const LOOPBACK_FAILURE_PAGE: &str = "<!doctype html><html><head><title>GitHub Notifier</title></head><body><p>You weren't signed in. You can close this tab and try again from GitHub Notifier.</p></body></html>";
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingClientConfiguration(&'static str),
+    Timeout,
+    Cancelled,
+    AccessDenied,
+    StateMismatch,
+    InvalidRedirect(String),
+    /// GitHub refused to issue a token, e.g. for a used or revoked refresh token
+    TokenExchange(String),
+    /// The token endpoint answered with something other than a token or an OAuth error
+    UnexpectedResponse(String),
+    InvalidToken(String),
+    Browser(String),
+    Storage(String),
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Network(NetworkError),
+    GitHub(GitHubError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingClientConfiguration(variable) => {
+                write!(
+                    f,
+                    "GitHub Notifier is missing its OAuth configuration ({} is not set).",
+                    variable
+                )
+            }
+            AuthError::Timeout => write!(f, "Sign in took too long and has timed out."),
+            AuthError::Cancelled => write!(f, "Sign in was cancelled."),
+            AuthError::AccessDenied => write!(f, "Access to your GitHub account was denied."),
+            AuthError::StateMismatch => {
+                write!(
+                    f,
+                    "The sign in response doesn't match the request that was sent."
+                )
+            }
+            AuthError::InvalidRedirect(reason) => write!(f, "Invalid sign in response: {}", reason),
+            AuthError::TokenExchange(reason) => {
+                write!(f, "Couldn't obtain an access token: {}", reason)
+            }
+            AuthError::UnexpectedResponse(reason) => {
+                write!(f, "GitHub sent an unexpected response: {}", reason)
+            }
+            AuthError::InvalidToken(reason) => write!(f, "{}", reason),
+            AuthError::Browser(reason) => write!(f, "Couldn't open the browser: {}", reason),
+            AuthError::Storage(reason) => write!(f, "Couldn't save the credentials: {}", reason),
+            AuthError::Io(error) => write!(f, "{}", error),
+            AuthError::Http(error) => write!(f, "Request to GitHub failed: {}", error),
+            AuthError::Network(error) => write!(f, "{}", error),
+            AuthError::GitHub(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl AuthError {
+    /// Whether trying again later is likely to succeed, e.g. once the network is back
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AuthError::Http(_) | AuthError::UnexpectedResponse(_) => true,
+            AuthError::GitHub(error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+impl From<std::io::Error> for AuthError {
+    fn from(error: std::io::Error) -> Self {
+        AuthError::Io(error)
+    }
+}
+
+impl From<reqwest::Error> for AuthError {
+    fn from(error: reqwest::Error) -> Self {
+        AuthError::Http(error)
+    }
+}
+
+impl From<NetworkError> for AuthError {
+    fn from(error: NetworkError) -> Self {
+        AuthError::Network(error)
+    }
+}
+
+impl From<GitHubError> for AuthError {
+    fn from(error: GitHubError) -> Self {
+        AuthError::GitHub(error)
+    }
+}
+
+fn client_id(host: &Host) -> Result<String, AuthError> {
+    host.client_id
+        .clone()
+        .ok_or(AuthError::MissingClientConfiguration("GITHUB_CLIENT_ID"))
+}
+
+fn client_secret() -> Result<String, AuthError> {
+    std::env::var("GITHUB_CLIENT_SECRET")
+        .map_err(|_| AuthError::MissingClientConfiguration("GITHUB_CLIENT_SECRET"))
+}
+
+/// oauth2's own `async_http_client` builds a default client, so token requests are sent through
+/// one created from the network configuration instead
+async fn oauth_http_client(
+    http_client: &reqwest::Client,
+    request: oauth2::HttpRequest,
+) -> Result<oauth2::HttpResponse, reqwest::Error> {
+    let method = reqwest::Method::from_bytes(request.method.as_str().as_bytes())
+        .unwrap_or(reqwest::Method::POST);
+    let mut request_builder = http_client
+        .request(method, request.url.as_str())
+        .body(request.body);
+    for (name, value) in &request.headers {
+        request_builder = request_builder.header(name.as_str(), value.as_bytes());
+    }
 
-use crate::constants::{AuthRedirectEventPayload, AUTH_REDIRECT_EVENT};
+    let response = request_builder.send().await?;
+    let status_code = oauth2::http::StatusCode::from_u16(response.status().as_u16())
+        .unwrap_or(oauth2::http::StatusCode::INTERNAL_SERVER_ERROR);
+    let mut headers = oauth2::http::HeaderMap::new();
+    for (name, value) in response.headers() {
+        if let (Ok(name), Ok(value)) = (
+            oauth2::http::HeaderName::from_bytes(name.as_str().as_bytes()),
+            oauth2::http::HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            headers.append(name, value);
+        }
+    }
+
+    Ok(oauth2::HttpResponse {
+        status_code,
+        headers,
+        body: response.bytes().await?.to_vec(),
+    })
+}
+
+/// GitHub answers token requests it refuses with 200 and an OAuth error, which oauth2 can only
+/// report as an unparsable token
+fn token_exchange_error(error: RequestTokenError<reqwest::Error, BasicErrorResponse>) -> AuthError {
+    match error {
+        RequestTokenError::ServerResponse(response) => {
+            AuthError::TokenExchange(response.to_string())
+        }
+        RequestTokenError::Request(error) => AuthError::Http(error),
+        RequestTokenError::Parse(error, body) => {
+            match serde_json::from_slice::<BasicErrorResponse>(&body) {
+                Ok(response) => AuthError::TokenExchange(response.to_string()),
+                Err(_) => AuthError::UnexpectedResponse(error.to_string()),
+            }
+        }
+        RequestTokenError::Other(reason) => AuthError::UnexpectedResponse(reason),
+    }
+}
+
+/// Token endpoints shouldn't be followed anywhere else, same as oauth2's own client
+fn oauth_token_client(network: &NetworkConfig) -> Result<reqwest::Client, AuthError> {
+    Ok(network
+        .client_builder()?
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?)
+}
+
+fn oauth_client(host: &Host) -> Result<BasicClient, AuthError> {
+    Ok(BasicClient::new(
+        ClientId::new(client_id(host)?),
+        Some(ClientSecret::new(client_secret()?)),
+        AuthUrl::from_url(host.web("login/oauth/authorize")),
+        Some(TokenUrl::from_url(host.web("login/oauth/access_token"))),
+    ))
+}
 
 pub async fn get_token(
     app: &tauri::AppHandle,
-) -> Result<StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>, Box<dyn std::error::Error>>
-{
-    let client = BasicClient::new(
-        ClientId::new(std::env::var("GITHUB_CLIENT_ID").expect("GITHUB_CLIENT_ID must be set.")),
-        Some(ClientSecret::new(
-            std::env::var("GITHUB_CLIENT_SECRET").expect("GITHUB_CLIENT_SECRET must be set."),
-        )),
-        AuthUrl::new("https://github.com/login/oauth/authorize".to_string()).unwrap(),
-        Some(TokenUrl::new("https://github.com/login/oauth/access_token".to_string()).unwrap()),
-    )
-    .set_redirect_uri(RedirectUrl::new("github-notifier://auth".to_string()).unwrap());
+    host: &Host,
+    network: &NetworkConfig,
+) -> Result<StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>, AuthError> {
+    // Without a registered `github-notifier://` handler the browser can't hand the code back
+    // to the app, so the redirect is captured by a one-shot listener on a random local port
+    let loopback_listener = if is_deep_link_registered(app) {
+        None
+    } else {
+        Some(TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?)
+    };
+    let redirect_url = match &loopback_listener {
+        Some(listener) => format!(
+            "http://127.0.0.1:{}/callback",
+            listener.local_addr()?.port()
+        ),
+        None => "github-notifier://auth".to_string(),
+    };
+
+    let client = oauth_client(host)?.set_redirect_uri(RedirectUrl::new(redirect_url).unwrap());
+    let http_client = oauth_token_client(network)?;
 
     let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
@@ -33,45 +236,428 @@ pub async fn get_token(
         .set_pkce_challenge(pkce_challenge)
         .url();
 
+    let (tx, rx) = oneshot::channel();
+    match loopback_listener {
+        Some(listener) => {
+            tauri::async_runtime::spawn(async move {
+                if let Ok(Ok(url)) =
+                    tokio::time::timeout(AUTH_TIMEOUT, wait_for_loopback_redirect(listener)).await
+                {
+                    let _ = tx.send(url);
+                }
+            });
+        }
+        None => {
+            app.once(AUTH_REDIRECT_EVENT, |event| {
+                if let Ok(AuthRedirectEventPayload { url }) = serde_json::from_str(event.payload())
+                {
+                    let _ = tx.send(url);
+                }
+            });
+        }
+    }
+
     app.opener()
         .open_url(auth_url.as_str(), None::<&str>)
-        .unwrap();
+        .map_err(|error| AuthError::Browser(error.to_string()))?;
 
     let (code, state) = {
-        let (tx, rx) = oneshot::channel();
-        app.once(AUTH_REDIRECT_EVENT, |event| {
-            if let Ok(AuthRedirectEventPayload { url }) = serde_json::from_str(event.payload()) {
-                tx.send(url).unwrap()
+        let request_line = tokio::time::timeout(AUTH_TIMEOUT, rx)
+            .await
+            .map_err(|_| AuthError::Timeout)?
+            .map_err(|_| AuthError::InvalidRedirect("no response was received".to_owned()))?;
+        let url = Url::parse(&request_line)
+            .map_err(|error| AuthError::InvalidRedirect(error.to_string()))?;
+        let query_param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        match query_param("error").as_deref() {
+            Some("access_denied") => return Err(AuthError::AccessDenied),
+            Some(error) => {
+                return Err(AuthError::InvalidRedirect(
+                    query_param("error_description").unwrap_or_else(|| error.to_owned()),
+                ))
             }
-        });
+            None => {}
+        }
 
-        let request_line = tokio::time::timeout(Duration::from_secs(60 * 5), rx)
-            .await
-            .unwrap()
-            .unwrap();
-        let url = Url::parse(&request_line).unwrap();
-        let code = url
-            .query_pairs()
-            .find(|(key, _)| key == "code")
-            .map(|(_, code)| oauth2::AuthorizationCode::new(code.into_owned()))
-            .unwrap();
-        let state = url
-            .query_pairs()
-            .find(|(key, _)| key == "state")
-            .map(|(_, state)| oauth2::CsrfToken::new(state.into_owned()))
-            .unwrap();
+        let code = query_param("code")
+            .map(oauth2::AuthorizationCode::new)
+            .ok_or_else(|| AuthError::InvalidRedirect("missing code".to_owned()))?;
+        let state = query_param("state")
+            .map(oauth2::CsrfToken::new)
+            .ok_or_else(|| AuthError::InvalidRedirect("missing state".to_owned()))?;
 
         (code, state)
     };
 
-    assert!(csrf_token.secret() == state.secret());
+    if csrf_token.secret() != state.secret() {
+        return Err(AuthError::StateMismatch);
+    }
 
     let token_response = client
         .exchange_code(code)
         .set_pkce_verifier(pkce_verifier)
-        .request_async(oauth2::reqwest::async_http_client)
+        .request_async(|request| oauth_http_client(&http_client, request))
         .await
-        .unwrap();
+        .map_err(token_exchange_error)?;
 
     Ok(token_response)
 }
+
+/// Exchanges the refresh token of an expiring GitHub App user token for a new token pair
+pub async fn refresh_token(
+    host: &Host,
+    network: &NetworkConfig,
+    refresh_token: &str,
+) -> Result<BasicTokenResponse, AuthError> {
+    let http_client = oauth_token_client(network)?;
+
+    oauth_client(host)?
+        .exchange_refresh_token(&RefreshToken::new(refresh_token.to_owned()))
+        .request_async(|request| oauth_http_client(&http_client, request))
+        .await
+        .map_err(token_exchange_error)
+}
+
+fn is_deep_link_registered(app: &tauri::AppHandle) -> bool {
+    #[cfg(any(windows, target_os = "linux"))]
+    {
+        use tauri_plugin_deep_link::DeepLinkExt;
+        app.deep_link()
+            .is_registered("github-notifier")
+            .unwrap_or(false)
+    }
+
+    // Schemes are declared in the app bundle on other platforms
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        let _ = app;
+        true
+    }
+}
+
+/// Accepts connections until the browser requests `/callback` and returns the full redirect URL.
+/// Each connection is read on its own, so speculative ones the browser never uses don't hold up the rest.
+pub async fn wait_for_loopback_redirect(listener: TcpListener) -> std::io::Result<String> {
+    let port = listener.local_addr()?.port();
+    let (tx, mut rx) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        loop {
+            let accepted = match future::select(pin!(listener.accept()), pin!(tx.closed())).await {
+                Either::Left((accepted, _)) => accepted,
+                // Nobody waits for the redirect anymore, e.g. sign in timed out
+                Either::Right(_) => break,
+            };
+
+            match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn({
+                        let tx = tx.clone();
+                        async move {
+                            if let Err(error) = answer_loopback_request(stream, port, tx).await {
+                                println!("Failed to answer sign in request: {:?}", error);
+                            }
+                        }
+                    });
+                }
+                Err(error) => {
+                    let _ = tx.send(Err(error)).await;
+                    break;
+                }
+            }
+        }
+    });
+
+    rx.recv()
+        .await
+        .unwrap_or_else(|| Err(std::io::ErrorKind::UnexpectedEof.into()))
+}
+
+/// Answers a single browser request, passing the redirect URL on if it's the `/callback` one
+async fn answer_loopback_request(
+    mut stream: TcpStream,
+    port: u16,
+    tx: mpsc::Sender<std::io::Result<String>>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.split();
+
+    let mut request_line = String::new();
+    tokio::time::timeout(
+        LOOPBACK_READ_TIMEOUT,
+        BufReader::new(reader).read_line(&mut request_line),
+    )
+    .await
+    .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+
+    // Request line looks like `GET /callback?code=...&state=... HTTP/1.1`
+    match request_line.split_whitespace().nth(1) {
+        Some(path) if path.starts_with("/callback") => {
+            let url = format!("http://127.0.0.1:{}{}", port, path);
+            // e.g. `error=access_denied` when the user cancels on GitHub
+            let failed = Url::parse(&url)
+                .map(|url| url.query_pairs().any(|(key, _)| key == "error"))
+                .unwrap_or(true);
+            let page = if failed {
+                LOOPBACK_FAILURE_PAGE
+            } else {
+                LOOPBACK_SUCCESS_PAGE
+            };
+
+            writer
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        page.len(),
+                        page
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+
+            let _ = tx.send(Ok(url)).await;
+        }
+        _ => {
+            writer
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+// GitHub answers pending/failed polls with `200 OK` and an `error` field, so both
+// shapes have to be accepted from the same response.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum DeviceTokenPollResponse {
+    Token(BasicTokenResponse),
+    Error {
+        error: String,
+        error_description: Option<String>,
+        interval: Option<u64>,
+    },
+}
+
+pub struct DeviceFlow {
+    http_client: reqwest::Client,
+    client_id: String,
+    device_code_url: Url,
+    token_url: Url,
+}
+
+impl DeviceFlow {
+    pub fn new(http_client: reqwest::Client, client_id: String, host: &Host) -> Self {
+        Self::with_endpoints(
+            http_client,
+            client_id,
+            host.web("login/device/code"),
+            host.web("login/oauth/access_token"),
+        )
+    }
+
+    pub fn with_endpoints(
+        http_client: reqwest::Client,
+        client_id: String,
+        device_code_url: Url,
+        token_url: Url,
+    ) -> Self {
+        Self {
+            http_client,
+            client_id,
+            device_code_url,
+            token_url,
+        }
+    }
+
+    pub async fn request_device_code(&self) -> Result<DeviceCodeResponse, AuthError> {
+        let response = self
+            .http_client
+            .post(self.device_code_url.clone())
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("scope", "notifications"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<DeviceCodeResponse>()
+            .await?;
+
+        Ok(response)
+    }
+
+    pub async fn poll_for_token(
+        &self,
+        device_code: &DeviceCodeResponse,
+    ) -> Result<BasicTokenResponse, AuthError> {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(device_code.expires_in);
+        let mut interval = Duration::from_secs(device_code.interval);
+
+        loop {
+            tokio::time::sleep(interval).await;
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(AuthError::Timeout);
+            }
+
+            let response = self
+                .http_client
+                .post(self.token_url.clone())
+                .header(reqwest::header::ACCEPT, "application/json")
+                .form(&[
+                    ("client_id", self.client_id.as_str()),
+                    ("device_code", device_code.device_code.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ])
+                .send()
+                .await?
+                .json::<DeviceTokenPollResponse>()
+                .await?;
+
+            match response {
+                DeviceTokenPollResponse::Token(token) => return Ok(token),
+                DeviceTokenPollResponse::Error {
+                    error,
+                    error_description,
+                    interval: new_interval,
+                } => match error.as_str() {
+                    "authorization_pending" => {}
+                    "slow_down" => {
+                        interval = new_interval
+                            .map(Duration::from_secs)
+                            .unwrap_or(interval + Duration::from_secs(5));
+                    }
+                    "expired_token" => return Err(AuthError::Timeout),
+                    "access_denied" => return Err(AuthError::AccessDenied),
+                    _ => return Err(AuthError::TokenExchange(error_description.unwrap_or(error))),
+                },
+            }
+        }
+    }
+}
+
+pub async fn get_token_with_device_flow(
+    app: &tauri::AppHandle,
+    host: &Host,
+    network: &NetworkConfig,
+) -> Result<StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>, AuthError> {
+    let device_flow = DeviceFlow::new(network.client()?, client_id(host)?, host);
+    let device_code = device_flow.request_device_code().await?;
+
+    app.opener()
+        .open_url(device_code.verification_uri.as_str(), None::<&str>)
+        .map_err(|error| AuthError::Browser(error.to_string()))?;
+
+    // Not blocking, so the code stays readable next to the browser while polling
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    app.dialog()
+        .message(format!(
+            "Enter the code {} on {} to sign in to GitHub Notifier.\n\nYou can hide this once you've entered it.",
+            device_code.user_code, device_code.verification_uri
+        ))
+        .title("Sign in with GitHub")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Hide".to_owned(),
+            "Cancel sign in".to_owned(),
+        ))
+        .show(move |hide| {
+            if !hide {
+                let _ = cancel_tx.send(());
+            }
+        });
+
+    let polling = pin!(device_flow.poll_for_token(&device_code));
+    let result = match future::select(polling, cancel_rx).await {
+        Either::Left((result, _)) => result,
+        Either::Right((Ok(()), _)) => Err(AuthError::Cancelled),
+        // Hidden without cancelling
+        Either::Right((Err(_), polling)) => polling.await,
+    };
+
+    result
+}
+
+pub async fn validate_personal_access_token(
+    token: &str,
+    host: &Host,
+    network: &NetworkConfig,
+) -> Result<(), AuthError> {
+    const FINE_GRAINED_TOKEN_ERROR: &str = "Fine-grained personal access tokens can't access notifications. Use a classic token with the `notifications` scope instead.";
+
+    if token.starts_with("github_pat_") {
+        return Err(AuthError::InvalidToken(FINE_GRAINED_TOKEN_ERROR.to_owned()));
+    }
+
+    let response = network
+        .client()?
+        .get(host.api("user"))
+        .header(reqwest::header::USER_AGENT, "Github Notifier")
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .bearer_auth(token)
+        .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(AuthError::InvalidToken(
+            "The token is invalid or has expired.".to_owned(),
+        ));
+    }
+
+    // Only classic tokens report their scopes, fine-grained ones don't send this header at all
+    let scopes = match response.error_for_status()?.headers().get("X-OAuth-Scopes") {
+        Some(scopes) => scopes.to_str().unwrap_or_default().to_owned(),
+        None => return Err(AuthError::InvalidToken(FINE_GRAINED_TOKEN_ERROR.to_owned())),
+    };
+
+    if !scopes
+        .split(',')
+        .any(|scope| scope.trim() == "notifications")
+    {
+        return Err(AuthError::InvalidToken(
+            "The token is missing the `notifications` scope.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Revokes the OAuth grant (and with it every token issued to the app for the user)
+pub async fn revoke_grant(
+    host: &Host,
+    network: &NetworkConfig,
+    token: &str,
+) -> Result<(), AuthError> {
+    let client_id = client_id(host)?;
+    let client_secret = client_secret()?;
+
+    network
+        .client()?
+        .delete(host.api(&format!("applications/{}/grant", client_id)))
+        .header(reqwest::header::USER_AGENT, "Github Notifier")
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .basic_auth(&client_id, Some(client_secret))
+        .json(&serde_json::json!({ "access_token": token }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::{fmt, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest;
+use url::Url;
+
+use crate::{credential_store::CredentialStoreKind, github::NotificationQuery};
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Host {
+    pub web_url: Url,
+    pub api_url: Url,
+    pub client_id: Option<String>,
+}
+
+impl Host {
+    pub fn github_com(client_id: Option<String>) -> Self {
+        Self {
+            web_url: Url::parse("https://github.com").unwrap(),
+            api_url: Url::parse("https://api.github.com").unwrap(),
+            client_id,
+        }
+    }
+
+    /// GitHub Enterprise Server serves its REST API from `/api/v3` on the web host
+    pub fn enterprise(web_url: Url, client_id: Option<String>) -> Self {
+        let api_url = join_path(&web_url, "api/v3");
+
+        Self {
+            web_url,
+            api_url,
+            client_id,
+        }
+    }
+
+    pub fn is_github_com(&self) -> bool {
+        self.web_url.host_str() == Some("github.com")
+    }
+
+    /// e.g. "github.com" or "github.example.com"
+    pub fn label(&self) -> &str {
+        self.web_url.host_str().unwrap_or(self.web_url.as_str())
+    }
+
+    pub fn api(&self, path: &str) -> Url {
+        join_path(&self.api_url, path)
+    }
+
+    pub fn web(&self, path: &str) -> Url {
+        join_path(&self.web_url, path)
+    }
+
+    /// GitHub Enterprise Server serves GraphQL from `/api/graphql` rather than next to the REST API
+    pub fn graphql(&self) -> Url {
+        if self.is_github_com() {
+            self.api("graphql")
+        } else {
+            self.web("api/graphql")
+        }
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        let client_id = std::env::var("GITHUB_CLIENT_ID").ok();
+        let web_url = std::env::var("GITHUB_URL")
+            .ok()
+            .and_then(|url| Url::parse(&url).ok());
+        let api_url = std::env::var("GITHUB_API_URL")
+            .ok()
+            .and_then(|url| Url::parse(&url).ok());
+
+        let host = match web_url {
+            Some(web_url) if web_url.host_str() != Some("github.com") => {
+                Self::enterprise(web_url, client_id)
+            }
+            _ => Self::github_com(client_id),
+        };
+
+        match api_url {
+            Some(api_url) => Self { api_url, ..host },
+            None => host,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Host signed in to when no other is picked
+    pub host: Host,
+    /// Further hosts accounts can be added for, e.g. a GitHub Enterprise Server next to github.com
+    pub extra_hosts: Vec<Host>,
+    pub credential_store: CredentialStoreKind,
+    /// Which notifications are polled, e.g. only participating ones or a single repository
+    pub notifications: NotificationQuery,
+    pub network: NetworkConfig,
+}
+
+impl Config {
+    /// Reads `config.json` from the app config directory, falling back to defaults
+    /// (and `GITHUB_*` environment variables) when it doesn't exist. A file that exists
+    /// but can't be parsed is an error, so the user can be told their settings were ignored
+    pub fn load(app: &AppHandle) -> Result<Self, serde_json::Error> {
+        match app
+            .path()
+            .app_config_dir()
+            .ok()
+            .and_then(|dir| std::fs::read_to_string(dir.join(CONFIG_FILE_NAME)).ok())
+        {
+            Some(content) => serde_json::from_str(&content),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Hosts offered when adding an account, github.com is always among them
+    pub fn hosts(&self) -> Vec<Host> {
+        let mut hosts = vec![self.host.clone()];
+        for host in &self.extra_hosts {
+            if !hosts.iter().any(|known| known.web_url == host.web_url) {
+                hosts.push(host.clone());
+            }
+        }
+        if !hosts.iter().any(Host::is_github_com) {
+            hosts.push(Host::github_com(std::env::var("GITHUB_CLIENT_ID").ok()));
+        }
+
+        hosts
+    }
+}
+
+/// Proxy and certificates applied to every HTTP client the app creates
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Without one the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables are used
+    pub proxy: Option<ProxyConfig>,
+    /// PEM files with root certificates trusted in addition to the system ones
+    pub ca_certificates: Vec<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProxyConfig {
+    pub url: Url,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Hosts that bypass the proxy, in the same comma separated format as `NO_PROXY`
+    pub no_proxy: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Proxy(reqwest::Error),
+    Certificate {
+        path: PathBuf,
+        message: String,
+    },
+    /// Building the client failed, e.g. the TLS backend rejected a certificate
+    Client(reqwest::Error),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Proxy(error) => write!(f, "Invalid proxy configuration: {}", error),
+            NetworkError::Certificate { path, message } => {
+                write!(
+                    f,
+                    "Couldn't load CA certificates from {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            NetworkError::Client(error) => write!(f, "Couldn't create the HTTP client: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl NetworkConfig {
+    /// Client builder with the proxy and extra certificates applied, for callers that need
+    /// to add their own defaults
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder, NetworkError> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(proxy_config) = &self.proxy {
+            let mut proxy =
+                reqwest::Proxy::all(proxy_config.url.clone()).map_err(NetworkError::Proxy)?;
+            if let Some(username) = &proxy_config.username {
+                proxy = proxy.basic_auth(
+                    username,
+                    proxy_config.password.as_deref().unwrap_or_default(),
+                );
+            }
+            proxy = proxy.no_proxy(
+                proxy_config
+                    .no_proxy
+                    .as_deref()
+                    .and_then(reqwest::NoProxy::from_string),
+            );
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.ca_certificates {
+            let certificate_error = |message: String| NetworkError::Certificate {
+                path: path.clone(),
+                message,
+            };
+            let pem = std::fs::read(path).map_err(|error| certificate_error(error.to_string()))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|error| certificate_error(error.to_string()))?;
+            if certificates.is_empty() {
+                return Err(certificate_error("no certificates found".to_owned()));
+            }
+
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        Ok(builder)
+    }
+
+    pub fn client(&self) -> Result<reqwest::Client, NetworkError> {
+        self.client_builder()?.build().map_err(NetworkError::Client)
+    }
+}
+
+fn join_path(base: &Url, path: &str) -> Url {
+    let mut url = base.clone();
+    url.set_path(&format!(
+        "{}/{}",
+        base.path().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    ));
+
+    url
+}
@@ -1,4 +1,6 @@
 pub const AUTH_REDIRECT_EVENT: &str = "auth_redirect";
+pub const PERSONAL_ACCESS_TOKEN_WINDOW: &str = "personal-access-token";
+pub const HTML_URL_CACHE_FILE_NAME: &str = "html_urls.json";
 
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct AuthRedirectEventPayload {
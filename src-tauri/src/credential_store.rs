@@ -0,0 +1,275 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use base64::Engine;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+const KEYRING_SERVICE: &str = "github-notifier";
+const CREDENTIALS_FILE_NAME: &str = "credentials.json";
+const KEY_FILE_NAME: &str = "credentials.key";
+const PASSPHRASE_VARIABLE: &str = "GITHUB_NOTIFIER_PASSPHRASE";
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStoreKind {
+    /// OS keyring, or the encrypted file when the keyring isn't usable
+    #[default]
+    Auto,
+    Keyring,
+    EncryptedFile,
+}
+
+pub trait CredentialStore: Send + Sync {
+    /// Human readable name of the backend, shown in the tray menu
+    fn name(&self) -> &'static str;
+    fn get(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>>;
+    fn set(&self, id: &str, secret: &str) -> Result<(), Box<dyn std::error::Error>>;
+    fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+pub fn open(
+    kind: CredentialStoreKind,
+    data_dir: Option<&Path>,
+) -> Result<Box<dyn CredentialStore>, Box<dyn std::error::Error>> {
+    let open_encrypted_file = || -> Result<Box<dyn CredentialStore>, Box<dyn std::error::Error>> {
+        let data_dir = data_dir.ok_or("App data directory is not available")?;
+        Ok(Box::new(EncryptedFileStore::open(data_dir)?))
+    };
+
+    match kind {
+        CredentialStoreKind::Keyring => Ok(Box::new(KeyringStore)),
+        CredentialStoreKind::EncryptedFile => open_encrypted_file(),
+        CredentialStoreKind::Auto if KeyringStore::is_available() => Ok(Box::new(KeyringStore)),
+        CredentialStoreKind::Auto => open_encrypted_file(),
+    }
+}
+
+/// Keeps credentials for the current session only, used when no other store could be opened
+#[derive(Default)]
+pub struct MemoryStore(Mutex<HashMap<String, String>>);
+
+impl CredentialStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "Memory (not saved)"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok(self.0.lock().unwrap().get(id).cloned())
+    }
+
+    fn set(&self, id: &str, secret: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(id.to_owned(), secret.to_owned());
+
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.0.lock().unwrap().remove(id);
+
+        Ok(())
+    }
+}
+
+pub struct KeyringStore;
+
+impl KeyringStore {
+    /// Headless sessions without a Secret Service fail any keyring access, not just missing entries
+    fn is_available() -> bool {
+        match keyring::Entry::new(KEYRING_SERVICE, "availability-check")
+            .and_then(|entry| entry.get_password())
+        {
+            Ok(_) | Err(keyring::Error::NoEntry) => true,
+            Err(error) => {
+                println!("OS keyring is not available: {:?}", error);
+                false
+            }
+        }
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "OS keyring"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match keyring::Entry::new(KEYRING_SERVICE, id)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn set(&self, id: &str, secret: &str) -> Result<(), Box<dyn std::error::Error>> {
+        keyring::Entry::new(KEYRING_SERVICE, id)?.set_password(secret)?;
+
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match keyring::Entry::new(KEYRING_SERVICE, id)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct EncryptedFile {
+    /// Salt for deriving the key from a passphrase
+    salt: String,
+    /// Base64 encoded nonce followed by the ciphertext, per entry
+    entries: HashMap<String, String>,
+}
+
+/// Credentials encrypted with XChaCha20-Poly1305, using a key derived from the
+/// `GITHUB_NOTIFIER_PASSPHRASE` environment variable or a random key stored next to the file
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    cipher: XChaCha20Poly1305,
+    file: Mutex<EncryptedFile>,
+}
+
+impl EncryptedFileStore {
+    pub fn open(data_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open_with_passphrase(data_dir, std::env::var(PASSPHRASE_VARIABLE).ok().as_deref())
+    }
+
+    /// Without a passphrase the key is generated once and kept in the data directory
+    pub fn open_with_passphrase(
+        data_dir: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(data_dir)?;
+
+        let path = data_dir.join(CREDENTIALS_FILE_NAME);
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+
+                EncryptedFile {
+                    salt: base64::engine::general_purpose::STANDARD.encode(salt),
+                    entries: HashMap::new(),
+                }
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let key = match passphrase {
+            Some(passphrase) => pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(
+                passphrase.as_bytes(),
+                &base64::engine::general_purpose::STANDARD.decode(&file.salt)?,
+                PBKDF2_ROUNDS,
+            ),
+            None => Self::machine_key(data_dir)?,
+        };
+
+        Ok(Self {
+            path,
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+            file: Mutex::new(file),
+        })
+    }
+
+    fn machine_key(data_dir: &Path) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+        let path = data_dir.join(KEY_FILE_NAME);
+
+        match std::fs::read(&path) {
+            Ok(key) => key
+                .try_into()
+                .map_err(|_| "Credentials key file is corrupted".into()),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                let mut key = [0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                write_private_file(&path, &key)?;
+
+                Ok(key)
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save(&self, file: &EncryptedFile) -> Result<(), Box<dyn std::error::Error>> {
+        write_private_file(&self.path, serde_json::to_string_pretty(file)?.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "Encrypted file"
+    }
+
+    fn get(&self, id: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let Some(entry) = self.file.lock().unwrap().entries.get(id).cloned() else {
+            return Ok(None);
+        };
+
+        let entry = base64::engine::general_purpose::STANDARD.decode(entry)?;
+        if entry.len() < 24 {
+            return Err("Stored credentials are corrupted".into());
+        }
+        let (nonce, ciphertext) = entry.split_at(24);
+        let secret = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt stored credentials, is the passphrase correct?")?;
+
+        Ok(Some(String::from_utf8(secret)?))
+    }
+
+    fn set(&self, id: &str, secret: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| "Failed to encrypt credentials")?;
+
+        let mut file = self.file.lock().unwrap();
+        file.entries.insert(
+            id.to_owned(),
+            base64::engine::general_purpose::STANDARD
+                .encode([nonce.as_slice(), ciphertext.as_slice()].concat()),
+        );
+
+        self.save(&file)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = self.file.lock().unwrap();
+        file.entries.remove(id);
+
+        self.save(&file)
+    }
+}
+
+fn write_private_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(content)
+}
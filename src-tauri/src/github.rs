@@ -1,30 +1,129 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use base64::Engine;
-use futures::{stream, Stream, TryFutureExt};
-use serde::Deserialize;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{stream, Stream};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use tauri_plugin_http::reqwest;
 use url::Url;
 
+use crate::{
+    config::{Host, NetworkConfig, NetworkError},
+    html_url_cache::HtmlUrlCache,
+};
+
+const PER_PAGE: u32 = 100;
+const DEFAULT_POLL_INTERVAL: u64 = 60;
+/// Below this many remaining requests only polling is allowed to spend the budget
+const LOW_RATE_LIMIT_REMAINING: u64 = 100;
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+
 #[derive(Deserialize, Debug)]
 pub struct SomeGithubResponse {
     pub html_url: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Subject {
     pub title: String,
     pub url: Option<String>,
     pub latest_comment_url: Option<String>,
-    pub r#type: String,
+    pub r#type: SubjectType,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum SubjectType {
+    Issue,
+    PullRequest,
+    Commit,
+    Release,
+    Discussion,
+    CheckSuite,
+    RepositoryInvitation,
+    RepositoryVulnerabilityAlert,
+    RepositoryDependabotAlertsThread,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+impl SubjectType {
+    pub fn label(&self) -> &str {
+        match self {
+            SubjectType::Issue => "Issue",
+            SubjectType::PullRequest => "Pull request",
+            SubjectType::Commit => "Commit",
+            SubjectType::Release => "Release",
+            SubjectType::Discussion => "Discussion",
+            SubjectType::CheckSuite => "Workflow run",
+            SubjectType::RepositoryInvitation => "Repository invitation",
+            SubjectType::RepositoryVulnerabilityAlert
+            | SubjectType::RepositoryDependabotAlertsThread => "Security alert",
+            SubjectType::Unknown(r#type) => r#type,
+        }
+    }
+}
+
+/// Why the user received a notification, see
+/// https://docs.github.com/en/rest/activity/notifications#about-notification-reasons
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    ApprovalRequested,
+    Assign,
+    Author,
+    CiActivity,
+    Comment,
+    Invitation,
+    Manual,
+    MemberFeatureRequested,
+    Mention,
+    ReviewRequested,
+    SecurityAdvisoryCredit,
+    SecurityAlert,
+    StateChange,
+    Subscribed,
+    TeamMention,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+impl Reason {
+    pub fn label(&self) -> &str {
+        match self {
+            Reason::ApprovalRequested => "Approval requested",
+            Reason::Assign => "Assigned",
+            Reason::Author => "Author",
+            Reason::CiActivity => "CI activity",
+            Reason::Comment => "Commented",
+            Reason::Invitation => "Invited",
+            Reason::Manual => "Subscribed manually",
+            Reason::MemberFeatureRequested => "Feature requested",
+            Reason::Mention => "Mentioned",
+            Reason::ReviewRequested => "Review requested",
+            Reason::SecurityAdvisoryCredit => "Security advisory credit",
+            Reason::SecurityAlert => "Security alert",
+            Reason::StateChange => "State changed",
+            Reason::Subscribed => "Watching",
+            Reason::TeamMention => "Team mentioned",
+            Reason::Unknown(reason) => reason,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct Owner {
+    pub login: String,
     pub avatar_url: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Repository {
     pub id: i32,
     pub name: String,
@@ -33,17 +132,224 @@ pub struct Repository {
     pub owner: Owner,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct NotificationThread {
     pub id: String,
     pub repository: Repository,
     pub subject: Subject,
-    pub reason: String,
+    pub reason: Reason,
     pub unread: bool,
-    pub updated_at: Option<String>,
-    pub last_read_at: Option<String>,
+    pub updated_at: DateTime<Utc>,
+    pub last_read_at: Option<DateTime<Utc>>,
     pub url: String,
     pub subscription_url: String,
+    /// Filled in from GraphQL for issues and pull requests, when the query succeeds
+    #[serde(skip)]
+    pub details: Option<SubjectDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubjectState {
+    Open,
+    Closed,
+    Merged,
+    Draft,
+}
+
+impl SubjectState {
+    pub fn label(&self) -> &str {
+        match self {
+            SubjectState::Open => "Open",
+            SubjectState::Closed => "Closed",
+            SubjectState::Merged => "Merged",
+            SubjectState::Draft => "Draft",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewDecision {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+/// Issue or pull request details that the REST notifications API doesn't include
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubjectDetails {
+    pub state: SubjectState,
+    pub author: Option<String>,
+    pub labels: Vec<String>,
+    /// Only set for pull requests
+    pub review_decision: Option<ReviewDecision>,
+    pub comment_count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLError {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RepositoryNode {
+    issue: Option<SubjectNode>,
+    pull_request: Option<SubjectNode>,
+}
+
+#[derive(Deserialize, Debug)]
+struct LoginNode {
+    login: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NameNode {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Connection<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct TotalCount {
+    total_count: u32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SubjectNode {
+    state: String,
+    #[serde(default)]
+    is_draft: bool,
+    author: Option<LoginNode>,
+    labels: Option<Connection<NameNode>>,
+    #[serde(default)]
+    review_decision: Option<ReviewDecision>,
+    comments: TotalCount,
+}
+
+impl From<SubjectNode> for SubjectDetails {
+    fn from(node: SubjectNode) -> Self {
+        let state = match node.state.as_str() {
+            "MERGED" => SubjectState::Merged,
+            "CLOSED" => SubjectState::Closed,
+            _ if node.is_draft => SubjectState::Draft,
+            _ => SubjectState::Open,
+        };
+
+        Self {
+            state,
+            author: node.author.map(|author| author.login),
+            labels: node
+                .labels
+                .map(|labels| labels.nodes.into_iter().map(|label| label.name).collect())
+                .unwrap_or_default(),
+            review_decision: node.review_decision,
+            comment_count: node.comments.total_count,
+        }
+    }
+}
+
+const SUBJECT_DETAILS_FRAGMENTS: &str = "
+fragment issueDetails on Issue {
+  state
+  author { login }
+  labels(first: 10) { nodes { name } }
+  comments { totalCount }
+}
+fragment pullRequestDetails on PullRequest {
+  state
+  isDraft
+  author { login }
+  labels(first: 10) { nodes { name } }
+  reviewDecision
+  comments { totalCount }
+}";
+
+/// Filters for the notifications endpoint, see
+/// https://docs.github.com/en/rest/activity/notifications#list-notifications-for-the-authenticated-user
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct NotificationQuery {
+    /// Include notifications already marked as read
+    all: bool,
+    /// Only notifications the user is directly participating in or mentioned in
+    participating: bool,
+    /// Only notifications updated after this time
+    since: Option<DateTime<Utc>>,
+    /// Only notifications updated before this time
+    before: Option<DateTime<Utc>>,
+    /// `owner/repo` to only list notifications of a single repository
+    repository: Option<String>,
+}
+
+impl NotificationQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    pub fn participating(mut self, participating: bool) -> Self {
+        self.participating = participating;
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn before(mut self, before: DateTime<Utc>) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn repository(mut self, owner: &str, repo: &str) -> Self {
+        self.repository = Some(format!("{}/{}", owner, repo));
+        self
+    }
+
+    pub fn url(&self, host: &Host) -> Url {
+        let mut url = match &self.repository {
+            Some(repository) => host.api(&format!("repos/{}/notifications", repository)),
+            None => host.api("notifications"),
+        };
+
+        {
+            let mut query = url.query_pairs_mut();
+            if self.all {
+                query.append_pair("all", "true");
+            }
+            if self.participating {
+                query.append_pair("participating", "true");
+            }
+            if let Some(since) = &self.since {
+                query.append_pair("since", &since.to_rfc3339_opts(SecondsFormat::Secs, true));
+            }
+            if let Some(before) = &self.before {
+                query.append_pair("before", &before.to_rfc3339_opts(SecondsFormat::Secs, true));
+            }
+        }
+
+        url
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -52,14 +358,267 @@ pub struct User {
     pub login: String,
 }
 
+#[derive(Debug)]
+pub enum GitHubError {
+    /// The token was revoked or has expired
+    Unauthorized,
+    /// `sso_url` is set when an organization requires authorizing the token for SAML SSO
+    Forbidden {
+        sso_url: Option<String>,
+    },
+    /// `reset` is when requests are allowed again, if GitHub reported it
+    RateLimited {
+        reset: Option<SystemTime>,
+    },
+    NotFound,
+    /// Any other unexpected status, usually a 5xx
+    Server {
+        status: reqwest::StatusCode,
+        message: String,
+    },
+    Network(reqwest::Error),
+    Decode(reqwest::Error),
+    /// GraphQL query rejected as a whole, e.g. for missing scopes or being too complex
+    GraphQL(Vec<String>),
+    /// The proxy or certificates from the network configuration couldn't be applied
+    Configuration(NetworkError),
+}
+
+impl GitHubError {
+    async fn from_response(response: reqwest::Response) -> Self {
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED => GitHubError::Unauthorized,
+            reqwest::StatusCode::FORBIDDEN => GitHubError::Forbidden {
+                // X-GitHub-SSO: required; url=https://github.com/orgs/...
+                sso_url: response
+                    .headers()
+                    .get("X-GitHub-SSO")
+                    .and_then(|value| value.to_str().ok()?.split_once("url="))
+                    .map(|(_, url)| url.trim().to_owned()),
+            },
+            reqwest::StatusCode::NOT_FOUND => GitHubError::NotFound,
+            status => GitHubError::Server {
+                status,
+                message: response.text().await.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for GitHubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitHubError::Unauthorized => write!(f, "GitHub rejected the access token."),
+            GitHubError::Forbidden { sso_url: Some(url) } => {
+                write!(f, "The token has to be authorized for SAML SSO: {}", url)
+            }
+            GitHubError::Forbidden { sso_url: None } => {
+                write!(f, "The token isn't allowed to access this resource.")
+            }
+            GitHubError::RateLimited { reset: Some(reset) } => {
+                match reset.duration_since(SystemTime::now()) {
+                    Ok(wait_time) => write!(f, "Rate limit exceeded, retrying in {:?}.", wait_time),
+                    Err(_) => write!(f, "Rate limit exceeded."),
+                }
+            }
+            GitHubError::RateLimited { reset: None } => write!(f, "Rate limit exceeded."),
+            GitHubError::NotFound => write!(f, "The resource doesn't exist."),
+            GitHubError::Server { status, message } => {
+                write!(f, "GitHub responded with {}: {}", status, message)
+            }
+            GitHubError::Network(error) => write!(f, "Request to GitHub failed: {}", error),
+            GitHubError::Decode(error) => write!(f, "Unexpected response from GitHub: {}", error),
+            GitHubError::GraphQL(messages) => {
+                write!(f, "GitHub rejected the query: {}", messages.join("; "))
+            }
+            GitHubError::Configuration(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GitHubError {}
+
+impl From<reqwest::Error> for GitHubError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            GitHubError::Decode(error)
+        } else {
+            GitHubError::Network(error)
+        }
+    }
+}
+
+impl From<NetworkError> for GitHubError {
+    fn from(error: NetworkError) -> Self {
+        GitHubError::Configuration(error)
+    }
+}
+
+impl GitHubError {
+    /// Whether retrying the same request later is likely to succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            GitHubError::Server { .. } | GitHubError::Network(_) | GitHubError::Decode(_)
+        )
+    }
+}
+
+/// Outcome of a single notifications poll
+#[derive(Debug)]
+pub enum NotificationsUpdate {
+    /// Nothing changed since the previous poll
+    Unchanged,
+    Updated(Vec<NotificationThread>),
+    Failed(GitHubError),
+}
+
+/// Subscription to a notification thread or a repository
+#[derive(Deserialize, Debug, Clone)]
+pub struct Subscription {
+    pub subscribed: bool,
+    /// Ignored threads and repositories don't produce notifications, even when the user is mentioned
+    pub ignored: bool,
+    pub reason: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of marking many notifications as read at once
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkAsReadStatus {
+    Done,
+    /// Too many notifications to mark in a single request, GitHub finishes marking them in the background
+    Processing,
+}
+
+/// Polling state worth keeping across restarts, so the first poll after a launch is still conditional
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct StreamState {
+    /// Query the rest of the state belongs to
+    pub query: NotificationQuery,
+    pub last_modified: Option<String>,
+    /// Seconds between polls, from the last `X-Poll-Interval`
+    pub poll_interval: Option<u64>,
+    pub last_poll: Option<DateTime<Utc>>,
+    /// Number of notifications the last full response listed
+    pub unread_count: usize,
+}
+
+impl StreamState {
+    pub fn new(query: NotificationQuery) -> Self {
+        Self {
+            query,
+            ..Default::default()
+        }
+    }
+}
+
+/// State of the notifications stream, so it can be shown in the tray
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum StreamHealth {
+    #[default]
+    Healthy,
+    /// Polls failed `failures` times in a row with transient errors and are retried with a backoff
+    Retrying {
+        failures: u32,
+        next_attempt: SystemTime,
+    },
+    RateLimited {
+        reset: Option<SystemTime>,
+    },
+    /// The last poll failed with an error retrying won't fix, e.g. a missing SSO authorization
+    Failing(String),
+}
+
+/// Request budget reported by the API, GitHub counts it per token so it's shared by all clones of a client
+#[derive(Debug, Default)]
+struct RateLimit {
+    remaining: Option<u64>,
+    reset_at: Option<SystemTime>,
+    /// Set by a rate limited response, either from `Retry-After` or the primary limit reset
+    blocked_until: Option<SystemTime>,
+}
+
+impl RateLimit {
+    /// Records the budget reported by a response, returns `true` if the response was rate limited
+    fn update(&mut self, response: &reqwest::Response) -> bool {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+        };
+
+        // GraphQL and search have budgets of their own, only the REST one limits polling
+        let resource = response
+            .headers()
+            .get("X-RateLimit-Resource")
+            .and_then(|value| value.to_str().ok());
+        if resource.is_some_and(|resource| resource != "core") {
+            return false;
+        }
+
+        if let Some(remaining) = header("X-RateLimit-Remaining") {
+            self.remaining = Some(remaining);
+        }
+        if let Some(reset) = header("X-RateLimit-Reset") {
+            self.reset_at = Some(UNIX_EPOCH + Duration::from_secs(reset));
+        }
+
+        if matches!(
+            response.status(),
+            reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::TOO_MANY_REQUESTS
+        ) {
+            // Secondary limits send Retry-After, primary ones only exhaust the remaining count
+            if let Some(retry_after) = header("Retry-After") {
+                self.blocked_until = Some(SystemTime::now() + Duration::from_secs(retry_after));
+                return true;
+            } else if self.remaining == Some(0) {
+                self.blocked_until = self.reset_at;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn wait_time(&self) -> Option<Duration> {
+        [
+            self.blocked_until,
+            self.reset_at.filter(|_| self.remaining == Some(0)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()?
+        .duration_since(SystemTime::now())
+        .ok()
+    }
+
+    fn is_low(&self) -> bool {
+        self.remaining
+            .is_some_and(|remaining| remaining < LOW_RATE_LIMIT_REMAINING)
+            || self.wait_time().is_some()
+    }
+}
+
 #[derive(Clone)]
 pub struct GitHub {
     http_client: reqwest::Client,
+    rate_limit: Arc<Mutex<RateLimit>>,
+    health: Arc<Mutex<StreamHealth>>,
+    stream_state: Arc<Mutex<StreamState>>,
+    html_url_cache: Option<Arc<HtmlUrlCache>>,
+    pub host: Host,
     pub user: User,
 }
 
 impl GitHub {
-    pub async fn new(token: String) -> Self {
+    pub async fn new(
+        token: String,
+        host: Host,
+        network: &NetworkConfig,
+    ) -> Result<Self, GitHubError> {
         let mut headers = reqwest::header::HeaderMap::new();
         headers.append("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
         headers.append(
@@ -75,98 +634,368 @@ impl GitHub {
             format!("Bearer {}", &token).parse().unwrap(),
         );
 
-        let http_client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .unwrap();
-        let user = http_client
-            .get("https://api.github.com/user")
-            .send()
-            .and_then(|response| response.json::<User>())
-            .await
-            .unwrap();
+        let http_client = network.client_builder()?.default_headers(headers).build()?;
+        let rate_limit = Arc::new(Mutex::new(RateLimit::default()));
+        let response = http_client.get(host.api("user")).send().await?;
+        let user = check_response(&rate_limit, response)
+            .await?
+            .json::<User>()
+            .await?;
 
-        Self { http_client, user }
+        Ok(Self {
+            http_client,
+            rate_limit,
+            health: Arc::default(),
+            stream_state: Arc::default(),
+            html_url_cache: None,
+            host,
+            user,
+        })
+    }
+
+    /// Shares resolved notification URLs with other clients and across restarts
+    pub fn with_html_url_cache(mut self, cache: Arc<HtmlUrlCache>) -> Self {
+        self.html_url_cache = Some(cache);
+        self
+    }
+
+    /// Saves URLs resolved since the last flush, if a cache is attached
+    pub fn flush_html_url_cache(&self) {
+        if let Some(cache) = &self.html_url_cache {
+            cache.flush();
+        }
+    }
+
+    /// Sends a request, records the rate limit it reports and turns error statuses into errors
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, GitHubError> {
+        check_response(&self.rate_limit, request.send().await?).await
+    }
+
+    /// Sleeps until the rate limit resets if the budget is exhausted
+    async fn wait_for_rate_limit(&self) {
+        let wait_time = self.rate_limit.lock().unwrap().wait_time();
+
+        if let Some(wait_time) = wait_time {
+            println!("Rate limit exceeded, waiting {:?}", wait_time);
+            tokio::time::sleep(wait_time).await;
+        }
+    }
+
+    /// Whether non-essential calls, like resolving notification URLs or downloading avatars,
+    /// should be skipped to keep the remaining budget for polling
+    pub fn is_rate_limit_low(&self) -> bool {
+        self.rate_limit.lock().unwrap().is_low()
+    }
+
+    pub fn health(&self) -> StreamHealth {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// State of the last successful poll, for restoring the stream after a restart
+    pub fn stream_state(&self) -> StreamState {
+        self.stream_state.lock().unwrap().clone()
     }
 
     async fn fetch_notifications(
         &self,
+        query: &NotificationQuery,
         last_modified: Option<String>,
-    ) -> Result<(Option<Vec<NotificationThread>>, Option<u64>, Option<String>), reqwest::Error>
-    {
+    ) -> Result<(Option<Vec<NotificationThread>>, Option<u64>, Option<String>), GitHubError> {
         let mut headers = reqwest::header::HeaderMap::new();
-        if let Some(last_modified) = last_modified {
-            headers.append(
-                reqwest::header::IF_MODIFIED_SINCE,
-                reqwest::header::HeaderValue::from_str(&last_modified).unwrap(),
-            );
+        // A saved value that isn't a valid header, e.g. from a hand edited state file, just
+        // costs a full response
+        if let Some(last_modified) = last_modified
+            .as_deref()
+            .and_then(|value| reqwest::header::HeaderValue::from_str(value).ok())
+        {
+            headers.append(reqwest::header::IF_MODIFIED_SINCE, last_modified);
         }
+        let mut url = query.url(&self.host);
+        url.query_pairs_mut()
+            .append_pair("per_page", &PER_PAGE.to_string());
+
+        self.wait_for_rate_limit().await;
         let response = self
-            .http_client
-            .get("https://api.github.com/notifications")
-            .headers(headers)
-            .timeout(Duration::from_secs(60))
-            .send()
+            .send(
+                self.http_client
+                    .get(url)
+                    .headers(headers)
+                    .timeout(Duration::from_secs(60)),
+            )
             .await?;
         let interval_header = response
             .headers()
             .get("X-Poll-Interval")
             .and_then(|value| value.to_str().ok()?.parse().ok());
-        let last_modified = response
+        let new_last_modified = response
             .headers()
             .get("Last-Modified")
             .and_then(|value| value.to_str().ok().map(|s| s.to_owned()));
-        let notifications = match response.status() {
-            reqwest::StatusCode::OK => Some(response.json::<Vec<NotificationThread>>().await?),
-            reqwest::StatusCode::NOT_MODIFIED => None,
-            code => {
-                println!("Status code: {:?}", code);
-                println!(
-                    "Failed to fetch notifications: {:?}",
-                    response.text().await?
-                );
-                None
-            }
-        };
+        let mut next_page = next_page_url(response.headers());
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok((None, interval_header, new_last_modified));
+        }
+        let mut notifications = response.json::<Vec<NotificationThread>>().await?;
+
+        // Later pages are fetched unconditionally, If-Modified-Since describes the whole list
+        while let Some(url) = next_page {
+            self.wait_for_rate_limit().await;
+            let response = self
+                .send(self.http_client.get(url).timeout(Duration::from_secs(60)))
+                .await?;
+            next_page = next_page_url(response.headers());
+            notifications.extend(response.json::<Vec<NotificationThread>>().await?);
+        }
+
+        Ok((Some(notifications), interval_header, new_last_modified))
+    }
 
-        Ok((notifications, interval_header, last_modified))
+    /// Polls notifications, retrying transient failures with an exponential backoff.
+    /// The stream never ends on its own, failed polls are reported as they happen.
+    pub fn notifications_stream(
+        &self,
+        query: NotificationQuery,
+    ) -> impl Stream<Item = NotificationsUpdate> + '_ {
+        self.notifications_stream_with_state(StreamState::new(query))
     }
 
-    pub fn notifications_stream(&self) -> impl Stream<Item = Option<Vec<NotificationThread>>> + '_ {
+    /// Same as `notifications_stream`, but continues from a previously saved state: the first poll
+    /// waits for the rest of the saved interval and only asks for changes since the last one
+    pub fn notifications_stream_with_state(
+        &self,
+        state: StreamState,
+    ) -> impl Stream<Item = NotificationsUpdate> + '_ {
+        let period = Duration::from_secs(state.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL));
+        let first_poll_in = state
+            .last_poll
+            .and_then(|last_poll| {
+                (last_poll + chrono::Duration::from_std(period).ok()? - Utc::now())
+                    .to_std()
+                    .ok()
+            })
+            .unwrap_or_default();
+        let mut interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + first_poll_in, period);
+        // Don't burst missed polls after a backoff or the computer waking up
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let query = state.query.clone();
+        let last_modified = state.last_modified.clone();
+        *self.stream_state.lock().unwrap() = state;
+
         stream::unfold(
-            (tokio::time::interval(Duration::from_secs(60)), None),
-            move |(mut interval, last_modified)| async move {
-                interval.tick().await;
-
-                match self.fetch_notifications(last_modified.clone()).await {
-                    Ok((new_notifications, new_interval_duration, new_last_modified)) => {
-                        let last_modified_time = new_last_modified.or(last_modified);
-                        let interval = match new_interval_duration {
-                            Some(duration) if duration != interval.period().as_secs() => {
-                                println!(
-                                    "Changing interval duration to {:?}",
-                                    Duration::from_secs(duration)
-                                );
-                                let mut new_interval =
-                                    tokio::time::interval(Duration::from_secs(duration));
-                                new_interval.tick().await;
-
-                                new_interval
-                            }
-                            _ => interval,
-                        };
+            (interval, last_modified, 0, None),
+            move |(mut interval, last_modified, failures, retry_delay)| {
+                let query = query.clone();
 
-                        Some((new_notifications, (interval, last_modified_time)))
+                async move {
+                    if let Some(retry_delay) = retry_delay {
+                        tokio::time::sleep(retry_delay).await;
+                        interval.reset();
+                    } else {
+                        interval.tick().await;
                     }
-                    Err(e) => {
-                        println!("{:?}", e);
-                        None
+
+                    match self
+                        .fetch_notifications(&query, last_modified.clone())
+                        .await
+                    {
+                        Ok((new_notifications, new_interval_duration, new_last_modified)) => {
+                            self.set_health(StreamHealth::Healthy);
+
+                            let last_modified_time = new_last_modified.or(last_modified);
+                            let interval = match new_interval_duration {
+                                Some(duration) if duration != interval.period().as_secs() => {
+                                    println!(
+                                        "Changing interval duration to {:?}",
+                                        Duration::from_secs(duration)
+                                    );
+                                    let mut new_interval =
+                                        tokio::time::interval(Duration::from_secs(duration));
+                                    new_interval.set_missed_tick_behavior(
+                                        tokio::time::MissedTickBehavior::Delay,
+                                    );
+                                    new_interval.tick().await;
+
+                                    new_interval
+                                }
+                                _ => interval,
+                            };
+
+                            {
+                                let mut state = self.stream_state.lock().unwrap();
+                                state.last_modified = last_modified_time.clone();
+                                state.poll_interval = Some(interval.period().as_secs());
+                                state.last_poll = Some(Utc::now());
+                                if let Some(threads) = &new_notifications {
+                                    state.unread_count = threads.len();
+                                }
+                            }
+
+                            let update = match new_notifications {
+                                Some(mut threads) => {
+                                    self.attach_subject_details(&mut threads).await;
+                                    NotificationsUpdate::Updated(threads)
+                                }
+                                None => NotificationsUpdate::Unchanged,
+                            };
+
+                            Some((update, (interval, last_modified_time, 0, None)))
+                        }
+                        Err(e) => {
+                            println!("Failed to fetch notifications: {}", e);
+
+                            // Last-Modified is kept so a recovered poll is still conditional
+                            let (failures, retry_delay) = if e.is_transient() {
+                                (failures + 1, Some(backoff(failures + 1)))
+                            } else {
+                                (0, None)
+                            };
+                            self.set_health(match (&e, retry_delay) {
+                                (_, Some(retry_delay)) => StreamHealth::Retrying {
+                                    failures,
+                                    next_attempt: SystemTime::now() + retry_delay,
+                                },
+                                (GitHubError::RateLimited { reset }, _) => {
+                                    StreamHealth::RateLimited { reset: *reset }
+                                }
+                                (error, _) => StreamHealth::Failing(error.to_string()),
+                            });
+
+                            Some((
+                                NotificationsUpdate::Failed(e),
+                                (interval, last_modified, failures, retry_delay),
+                            ))
+                        }
                     }
                 }
             },
         )
     }
 
+    /// Fetches details of every issue and pull request in one GraphQL query, keyed by subject API URL
+    pub async fn fetch_subject_details(
+        &self,
+        threads: &[NotificationThread],
+    ) -> Result<HashMap<String, SubjectDetails>, GitHubError> {
+        let mut subjects: Vec<(String, &str, &str, &str, u32)> = Vec::new();
+        for thread in threads {
+            let Some(url) = &thread.subject.url else {
+                continue;
+            };
+            if subjects.iter().any(|(subject_url, ..)| subject_url == url) {
+                continue;
+            }
+            // e.g. https://api.github.com/repos/octocat/Hello-World/pulls/1347
+            let segments: Vec<&str> = url.rsplitn(5, '/').collect();
+            let (number, kind, name, owner) = match segments[..] {
+                [number, kind, name, owner, _] => (number, kind, name, owner),
+                _ => continue,
+            };
+            let (Ok(number), "issues" | "pulls") = (number.parse::<u32>(), kind) else {
+                continue;
+            };
+
+            subjects.push((url.clone(), owner, name, kind, number));
+        }
+
+        if subjects.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        // Aliases keep everything in one query, values go through variables so names are never quoted by hand
+        let mut variable_definitions = Vec::new();
+        let mut selections = Vec::new();
+        let mut variables = serde_json::Map::new();
+        for (index, (_, owner, name, kind, number)) in subjects.iter().enumerate() {
+            variable_definitions.push(format!(
+                "$owner{0}: String!, $name{0}: String!, $number{0}: Int!",
+                index
+            ));
+            let field = match *kind {
+                "pulls" => "pullRequest(number: $number{0}) { ...pullRequestDetails }",
+                _ => "issue(number: $number{0}) { ...issueDetails }",
+            }
+            .replace("{0}", &index.to_string());
+            selections.push(format!(
+                "s{0}: repository(owner: $owner{0}, name: $name{0}) {{ {1} }}",
+                index, field
+            ));
+            variables.insert(format!("owner{}", index), (*owner).into());
+            variables.insert(format!("name{}", index), (*name).into());
+            variables.insert(format!("number{}", index), (*number).into());
+        }
+        let query = format!(
+            "query({}) {{\n{}\n}}\n{}",
+            variable_definitions.join(", "),
+            selections.join("\n"),
+            SUBJECT_DETAILS_FRAGMENTS
+        );
+
+        // Inaccessible subjects come back as null next to an error, the rest are still usable
+        let response = self
+            .send(
+                self.http_client
+                    .post(self.host.graphql())
+                    .json(&serde_json::json!({ "query": query, "variables": variables })),
+            )
+            .await?
+            .json::<GraphQLResponse<HashMap<String, Option<RepositoryNode>>>>()
+            .await?;
+        let errors: Vec<String> = response
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        let Some(mut data) = response.data else {
+            return Err(GitHubError::GraphQL(errors));
+        };
+        if !errors.is_empty() {
+            println!(
+                "Some notification details couldn't be fetched: {}",
+                errors.join("; ")
+            );
+        }
+
+        Ok(subjects
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, (url, ..))| {
+                let repository = data.remove(&format!("s{}", index)).flatten()?;
+                let node = repository.pull_request.or(repository.issue)?;
+
+                Some((url, node.into()))
+            })
+            .collect())
+    }
+
+    /// Details are a nice to have, notifications are shown without them when they can't be fetched
+    async fn attach_subject_details(&self, threads: &mut [NotificationThread]) {
+        if self.is_rate_limit_low() {
+            return;
+        }
+
+        match self.fetch_subject_details(threads).await {
+            Ok(details) => {
+                for thread in threads.iter_mut() {
+                    thread.details = thread
+                        .subject
+                        .url
+                        .as_ref()
+                        .and_then(|url| details.get(url).cloned());
+                }
+            }
+            Err(error) => println!("Failed to fetch notification details: {}", error),
+        }
+    }
+
+    fn set_health(&self, health: StreamHealth) {
+        *self.health.lock().unwrap() = health;
+    }
+
     pub async fn generate_github_url(
         &self,
         notification_thread: &NotificationThread,
@@ -174,27 +1003,54 @@ impl GitHub {
     ) -> Option<url::Url> {
         let referrer_id = Self::generate_notification_referrer_id(&notification_thread.id, user_id);
 
-        match &notification_thread.subject {
+        let api_url = match &notification_thread.subject {
             Subject {
                 latest_comment_url: Some(url),
                 ..
-            } => self.fetch_html_url(url).await.ok(),
-            Subject { url: Some(url), .. } => self.fetch_html_url(url).await.ok(),
-            _ => None,
-        }
-        .and_then(|url| {
-            Url::parse_with_params(&url, &[("notification_referrer_id", referrer_id)]).ok()
+            } => url,
+            Subject { url: Some(url), .. } => url,
+            _ => return None,
+        };
+        let cached_url = self
+            .html_url_cache
+            .as_ref()
+            .and_then(|cache| cache.get(api_url, &notification_thread.updated_at));
+
+        let html_url = match cached_url {
+            Some(html_url) => html_url,
+            None => {
+                if self.is_rate_limit_low() {
+                    println!("Rate limit is low, not resolving notification URL");
+                    return None;
+                }
+
+                let html_url = self.fetch_html_url(api_url).await.ok()?;
+                if let Some(cache) = &self.html_url_cache {
+                    cache.insert(api_url, notification_thread.updated_at, &html_url);
+                }
+
+                html_url
+            }
+        };
+
+        Url::parse(&html_url).ok().map(|mut url| {
+            // Referrer ids are only understood by the web host the notification belongs to
+            if url.host_str() == self.host.web_url.host_str() {
+                url.query_pairs_mut()
+                    .append_pair("notification_referrer_id", &referrer_id);
+            }
+
+            url
         })
     }
 
-    pub async fn fetch_html_url(&self, url: &str) -> Result<String, reqwest::Error> {
-        self.http_client
-            .get(url)
-            .send()
+    pub async fn fetch_html_url(&self, url: &str) -> Result<String, GitHubError> {
+        self.send(self.http_client.get(url))
             .await?
             .json::<SomeGithubResponse>()
             .await
             .map(|response| response.html_url)
+            .map_err(GitHubError::from)
     }
 
     pub fn generate_notification_referrer_id(notification_id: &str, user_id: i32) -> String {
@@ -213,25 +1069,221 @@ impl GitHub {
         format!("NT_{}", referrer_id)
     }
 
-    pub async fn mark_thread_as_done(&self, thread_id: &str) -> Result<(), reqwest::Error> {
-        self.http_client
-            .delete(format!(
-                "https://api.github.com/notifications/threads/{}",
-                thread_id
-            ))
-            .send()
-            .await
-            .map(|_| ())
+    pub async fn mark_thread_as_done(&self, thread_id: &str) -> Result<(), GitHubError> {
+        self.send(
+            self.http_client.delete(
+                self.host
+                    .api(&format!("notifications/threads/{}", thread_id)),
+            ),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn mark_thread_as_read(&self, thread_id: &str) -> Result<(), GitHubError> {
+        self.send(
+            self.http_client.patch(
+                self.host
+                    .api(&format!("notifications/threads/{}", thread_id)),
+            ),
+        )
+        .await
+        .map(|_| ())
     }
 
-    pub async fn delete_thread_subscription(&self, thread_id: &str) -> Result<(), reqwest::Error> {
-        self.http_client
-            .delete(format!(
-                "https://api.github.com/notifications/threads/{}/subscription",
-                thread_id
-            ))
-            .send()
+    /// Marks notifications last updated before `last_read_at` as read
+    pub async fn mark_all_as_read(
+        &self,
+        last_read_at: DateTime<Utc>,
+    ) -> Result<MarkAsReadStatus, GitHubError> {
+        self.mark_as_read(self.host.api("notifications"), last_read_at)
             .await
-            .map(|_| ())
     }
+
+    /// Marks notifications of a repository last updated before `last_read_at` as read
+    pub async fn mark_repository_as_read(
+        &self,
+        owner: &str,
+        repo: &str,
+        last_read_at: DateTime<Utc>,
+    ) -> Result<MarkAsReadStatus, GitHubError> {
+        self.mark_as_read(
+            self.host
+                .api(&format!("repos/{}/{}/notifications", owner, repo)),
+            last_read_at,
+        )
+        .await
+    }
+
+    async fn mark_as_read(
+        &self,
+        url: Url,
+        last_read_at: DateTime<Utc>,
+    ) -> Result<MarkAsReadStatus, GitHubError> {
+        let response = self
+            .send(self.http_client.put(url).json(&serde_json::json!({
+                "last_read_at": last_read_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "read": true,
+            })))
+            .await?;
+
+        Ok(match response.status() {
+            reqwest::StatusCode::ACCEPTED => MarkAsReadStatus::Processing,
+            _ => MarkAsReadStatus::Done,
+        })
+    }
+
+    /// Fails with `GitHubError::NotFound` when the user has no explicit subscription to the thread
+    pub async fn get_thread_subscription(
+        &self,
+        thread_id: &str,
+    ) -> Result<Subscription, GitHubError> {
+        self.send(
+            self.http_client.get(
+                self.host
+                    .api(&format!("notifications/threads/{}/subscription", thread_id)),
+            ),
+        )
+        .await?
+        .json::<Subscription>()
+        .await
+        .map_err(GitHubError::from)
+    }
+
+    pub async fn set_thread_subscription(
+        &self,
+        thread_id: &str,
+        ignored: bool,
+    ) -> Result<Subscription, GitHubError> {
+        self.send(
+            self.http_client
+                .put(
+                    self.host
+                        .api(&format!("notifications/threads/{}/subscription", thread_id)),
+                )
+                .json(&serde_json::json!({ "ignored": ignored })),
+        )
+        .await?
+        .json::<Subscription>()
+        .await
+        .map_err(GitHubError::from)
+    }
+
+    /// Repositories the user is watching
+    pub async fn list_watched_repositories(&self) -> Result<Vec<Repository>, GitHubError> {
+        let mut url = self.host.api("user/subscriptions");
+        url.query_pairs_mut()
+            .append_pair("per_page", &PER_PAGE.to_string());
+
+        let mut repositories = Vec::new();
+        let mut next_page = Some(url);
+        while let Some(url) = next_page {
+            let response = self.send(self.http_client.get(url)).await?;
+            next_page = next_page_url(response.headers());
+            repositories.extend(response.json::<Vec<Repository>>().await?);
+        }
+
+        Ok(repositories)
+    }
+
+    /// Watches the repository, or ignores all of its notifications when `ignored` is set
+    pub async fn set_repository_subscription(
+        &self,
+        owner: &str,
+        repo: &str,
+        subscribed: bool,
+        ignored: bool,
+    ) -> Result<Subscription, GitHubError> {
+        self.send(
+            self.http_client
+                .put(
+                    self.host
+                        .api(&format!("repos/{}/{}/subscription", owner, repo)),
+                )
+                .json(&serde_json::json!({ "subscribed": subscribed, "ignored": ignored })),
+        )
+        .await?
+        .json::<Subscription>()
+        .await
+        .map_err(GitHubError::from)
+    }
+
+    /// Stops watching the repository, notifications about the user's own involvement still arrive
+    pub async fn delete_repository_subscription(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<(), GitHubError> {
+        self.send(
+            self.http_client.delete(
+                self.host
+                    .api(&format!("repos/{}/{}/subscription", owner, repo)),
+            ),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn delete_thread_subscription(&self, thread_id: &str) -> Result<(), GitHubError> {
+        self.send(
+            self.http_client.delete(
+                self.host
+                    .api(&format!("notifications/threads/{}/subscription", thread_id)),
+            ),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// Exponential backoff with jitter, so clients that failed together don't retry together
+pub fn backoff(failures: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY);
+
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Finds the `rel="next"` target in a `Link` header
+fn next_page_url(headers: &reqwest::header::HeaderMap) -> Option<Url> {
+    headers
+        .get(reqwest::header::LINK)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .find_map(|link| {
+            let (url, params) = link.split_once(';')?;
+            if !params
+                .split(';')
+                .any(|param| param.trim() == r#"rel="next""#)
+            {
+                return None;
+            }
+
+            Url::parse(url.trim().trim_start_matches('<').trim_end_matches('>')).ok()
+        })
+}
+
+/// Records the rate limit reported by a response and turns error statuses into errors
+async fn check_response(
+    rate_limit: &Mutex<RateLimit>,
+    response: reqwest::Response,
+) -> Result<reqwest::Response, GitHubError> {
+    let rate_limited = {
+        let mut rate_limit = rate_limit.lock().unwrap();
+        rate_limit
+            .update(&response)
+            .then_some(rate_limit.blocked_until)
+    };
+    if let Some(reset) = rate_limited {
+        return Err(GitHubError::RateLimited { reset });
+    }
+
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(GitHubError::from_response(response).await);
+    }
+
+    Ok(response)
 }
@@ -0,0 +1,93 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::json_file;
+
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct Entry {
+    api_url: String,
+    updated_at: DateTime<Utc>,
+    html_url: String,
+}
+
+/// Web URLs resolved from subject and comment API URLs, so a thread is only resolved again
+/// once it gets updated. Least recently used entries are dropped past `MAX_ENTRIES`.
+/// Changes are only written by `flush`, so a whole poll is saved at once.
+pub struct HtmlUrlCache {
+    path: Option<PathBuf>,
+    /// Least recently used first
+    entries: Mutex<Vec<Entry>>,
+    dirty: AtomicBool,
+}
+
+impl HtmlUrlCache {
+    /// Reads the cache from `path`, starting empty if it doesn't exist or can't be parsed
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_deref()
+            .and_then(json_file::load)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries: Mutex::new(entries),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&self, api_url: &str, updated_at: &DateTime<Utc>) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let index = entries
+            .iter()
+            .position(|entry| entry.api_url == api_url && entry.updated_at == *updated_at)?;
+
+        let entry = entries.remove(index);
+        let html_url = entry.html_url.clone();
+        entries.push(entry);
+
+        Some(html_url)
+    }
+
+    pub fn insert(&self, api_url: &str, updated_at: DateTime<Utc>, html_url: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| entry.api_url != api_url);
+        entries.push(Entry {
+            api_url: api_url.to_owned(),
+            updated_at,
+            html_url: html_url.to_owned(),
+        });
+
+        let overflow = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..overflow);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes the cache if anything was inserted since the last flush
+    pub fn flush(&self) {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        if let Err(error) = self.save() {
+            println!("Failed to save html_url cache: {:?}", error);
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        json_file::save(path, &*self.entries.lock().unwrap())
+    }
+}
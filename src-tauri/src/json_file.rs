@@ -0,0 +1,28 @@
+use std::{error::Error, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Reads a JSON file, `None` when it doesn't exist or can't be parsed. Parse errors are logged,
+/// since the next save overwrites the file.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = std::fs::read_to_string(path).ok()?;
+
+    serde_json::from_str(&content)
+        .inspect_err(|error| println!("Failed to parse {}: {}", path.display(), error))
+        .ok()
+}
+
+/// Writes the value next to the file and renames it over it, so a crash can't leave it half
+/// written
+pub fn save<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    std::fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    std::fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod config;
+pub mod constants;
+pub mod credential_store;
+pub mod github;
+pub mod html_url_cache;
+pub mod json_file;
+pub mod monitor;
@@ -1,27 +1,54 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use constants::{AuthRedirectEventPayload, AUTH_REDIRECT_EVENT};
-use futures::StreamExt;
-use oauth2::TokenResponse;
+use std::{
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use accounts::{
+    Account, Accounts, Clients, Credentials, CredentialsKind, MonitoringTasks, StreamHealths,
+    UnreadCounts, WatchedRepositories,
+};
+use auth::AuthError;
+use chrono::Utc;
+use config::{Config, Host};
+use constants::{
+    AuthRedirectEventPayload, AUTH_REDIRECT_EVENT, HTML_URL_CACHE_FILE_NAME,
+    PERSONAL_ACCESS_TOKEN_WINDOW,
+};
+use github::{GitHubError, MarkAsReadStatus, StreamHealth};
+use github_notifier_lib::{
+    auth, config, constants, credential_store, github,
+    html_url_cache::HtmlUrlCache,
+    json_file,
+    monitor::{self, NotificationMonitor, NotificationPresenter},
+};
+use muted_threads::MutedThreads;
+use stream_states::StreamStates;
 use tauri::{
-    menu::{MenuBuilder, MenuItemBuilder, PredefinedMenuItem},
+    menu::{MenuBuilder, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder},
     tray::TrayIconBuilder,
-    AppHandle, Emitter, Wry,
+    AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder, Wry,
 };
 use tauri_plugin_autostart::ManagerExt;
 use tauri_plugin_deep_link::DeepLinkExt;
-use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_opener::OpenerExt;
 use tauri_plugin_updater::UpdaterExt;
 
-mod auth;
-mod constants;
-mod github;
+mod accounts;
+mod muted_threads;
 mod notifications;
+mod stream_states;
 mod utils;
 
+/// Watched repositories listed in the tray per account, the rest are managed on GitHub
+const MAX_TRAY_WATCHED_REPOSITORIES: usize = 10;
+
 fn main() {
     dotenv::dotenv().ok();
 
@@ -50,6 +77,7 @@ fn main() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_notification::init())
+        .invoke_handler(tauri::generate_handler![sign_in_with_personal_access_token])
         .setup(setup)
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -62,7 +90,10 @@ fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(any(windows, target_os = "linux"))]
     {
         use tauri_plugin_deep_link::DeepLinkExt;
-        app.deep_link().register_all()?;
+        if let Err(error) = app.deep_link().register_all() {
+            // Sign in falls back to a loopback redirect when the scheme isn't registered
+            println!("Failed to register deep links: {:?}", error);
+        }
     }
 
     let autostart_manager = app.autolaunch();
@@ -72,6 +103,56 @@ fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     }
     let app_handle = app.handle().clone();
 
+    let config = Config::load(&app_handle).unwrap_or_else(|error| {
+        println!("Failed to parse config.json: {}", error);
+        app.dialog()
+            .message(format!(
+                "config.json couldn't be read: {}\n\nGitHub Notifier is using the default settings until it's fixed.",
+                error
+            ))
+            .title("Couldn't read settings")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
+
+        Config::default()
+    });
+    // A broken store shouldn't keep the app from starting, accounts just can't be remembered
+    let credential_store = credential_store::open(
+        config.credential_store,
+        app.path().app_data_dir().ok().as_deref(),
+    )
+    .unwrap_or_else(|error| {
+        println!("Failed to open credential store: {:?}", error);
+        app.dialog()
+            .message(format!(
+                "Stored credentials couldn't be opened: {}\n\nYou can still sign in, but you'll have to sign in again after restarting GitHub Notifier.",
+                error
+            ))
+            .title("Couldn't open stored credentials")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
+
+        Box::new(credential_store::MemoryStore::default())
+    });
+    println!("Storing credentials in: {}", credential_store.name());
+    app.manage(Accounts::load(&app_handle, &config.host, credential_store));
+    app.manage(UnreadCounts::default());
+    app.manage(StreamHealths::default());
+    app.manage(Clients::default());
+    app.manage(WatchedRepositories::default());
+    app.manage(MutedThreads::load(&app_handle));
+    app.manage(StreamStates::load(&app_handle));
+    app.manage(Arc::new(HtmlUrlCache::load(
+        app.path()
+            .app_cache_dir()
+            .ok()
+            .map(|dir| dir.join(HTML_URL_CACHE_FILE_NAME)),
+    )));
+    app.manage(MonitoringTasks::default());
+    app.manage(PersonalAccessTokenHost::default());
+    app.manage(utils::IconClient::default());
+    app.manage(config);
+
     tauri::async_runtime::spawn(check_updates(app.handle().clone()));
 
     match app.notification().permission_state().unwrap() {
@@ -100,139 +181,959 @@ fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
-    let token_entry = keyring::Entry::new("github-notifier", "user").unwrap();
-    let token = token_entry.get_password().ok();
-
-    setup_tray(&app_handle, token.is_some())?;
+    setup_tray(&app_handle)?;
 
-    if let Some(token) = token {
-        start_monitoring_notifications(app_handle.clone(), token)
+    for account in app.state::<Accounts>().list() {
+        if let Some(credentials) = app.state::<Accounts>().credentials(&account) {
+            start_monitoring_notifications(app_handle.clone(), account, credentials);
+        }
     }
 
     Ok(())
 }
 
 async fn check_updates(app_handle: AppHandle) {
-    if let Some(update) = app_handle.updater().unwrap().check().await.unwrap() {
-        let should_update_app = app_handle.dialog()
-            .message(format!("Version {} of GitHub Notifier is available (you have {}).\n\nDo you want to update?", update.version, update.current_version))
-            .title("New version of GitHub Notifier is available!")
-            .buttons(MessageDialogButtons::OkCancelCustom("Update".to_owned(), "Not now".to_owned()))
-            .blocking_show();
+    // The updater builds its own client, so only the proxy (with its credentials in the URL) carries over
+    let mut updater_builder = app_handle.updater_builder();
+    if let Some(proxy_config) = &app_handle.state::<Config>().network.proxy {
+        let mut proxy = proxy_config.url.clone();
+        if let Some(username) = &proxy_config.username {
+            let _ = proxy.set_username(username);
+            let _ = proxy.set_password(proxy_config.password.as_deref());
+        }
+        updater_builder = updater_builder.proxy(proxy);
+    }
+
+    let update = match updater_builder.build() {
+        Ok(updater) => updater.check().await,
+        Err(error) => Err(error),
+    };
+    let update = match update {
+        Ok(Some(update)) => update,
+        Ok(None) => return,
+        Err(error) => {
+            println!("Failed to check for updates: {}", error);
+            return;
+        }
+    };
+
+    let should_update_app = app_handle
+        .dialog()
+        .message(format!(
+            "Version {} of GitHub Notifier is available (you have {}).\n\nDo you want to update?",
+            update.version, update.current_version
+        ))
+        .title("New version of GitHub Notifier is available!")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Update".to_owned(),
+            "Not now".to_owned(),
+        ))
+        .blocking_show();
 
-        if should_update_app {
-            update.download_and_install(|_, _| {}, || {}).await.unwrap();
+    if should_update_app {
+        if let Err(error) = update.download_and_install(|_, _| {}, || {}).await {
+            println!("Failed to install update: {}", error);
         }
     }
 }
 
-fn start_monitoring_notifications(app_handle: tauri::AppHandle, token: String) {
-    tauri::async_runtime::spawn(async move {
-        let github = github::GitHub::new(token).await;
+fn start_monitoring_notifications(
+    app_handle: tauri::AppHandle,
+    account: Account,
+    credentials: Credentials,
+) {
+    let account_id = account.id.clone();
+    let task = tauri::async_runtime::spawn({
+        let app_handle = app_handle.clone();
 
-        // TODO: handle errors
-        github
-            .notifications_stream()
-            .for_each(|threads| async {
-                if let Some(threads) = threads {
-                    app_handle
-                        .tray_by_id("tray")
-                        .unwrap()
-                        .set_title(if threads.is_empty() {
-                            None
-                        } else {
-                            Some(threads.len().to_string())
-                        })
-                        .unwrap();
-
-                    if threads.len() < 5 {
-                        for thread in threads.iter() {
-                            let url = github
-                                .generate_github_url(thread, github.user.id)
-                                .await
-                                .map_or(String::from("https://github.com/notifications"), |url| {
-                                    url.into()
-                                });
-
-                            notifications::show_notification(
-                                thread,
-                                app_handle.clone(),
-                                url,
-                                &github,
-                            )
-                            .await
-                            .unwrap();
-                        }
-                    } else {
-                        app_handle
-                            .notification()
-                            .builder()
-                            .title("New notifications!")
-                            .body(format!("You have {} new notifications", threads.len()))
-                            .show()
-                            .unwrap();
+        async move {
+            // At login the network is often not up yet, so only a rejected token gives up
+            let client = retry(
+                &app_handle,
+                &account,
+                |error| !matches!(error, GitHubError::Unauthorized),
+                || {
+                    create_client(
+                        &app_handle,
+                        credentials.access_token.clone(),
+                        account.host.clone(),
+                    )
+                },
+            )
+            .await;
+            let (credentials, github) = match client {
+                Ok(github) => (credentials, github),
+                // The token was rejected
+                Err(_) => match renew_session(&app_handle, &account, &credentials).await {
+                    Ok(session) => session,
+                    Err(error) => {
+                        on_renewal_failed(&app_handle, &account, error);
+                        return;
                     }
+                },
+            };
+
+            if account.login.is_none() {
+                if let Err(error) = app_handle
+                    .state::<Accounts>()
+                    .set_login(&account.id, &github.user.login)
+                {
+                    println!("Failed to save login of {}: {:?}", account.id, error);
                 }
-            })
-            .await;
+                refresh_tray_menu(&app_handle);
+            }
+
+            monitor_notifications(app_handle, account, credentials, github).await;
+        }
     });
+
+    app_handle
+        .state::<MonitoringTasks>()
+        .insert(&account_id, task);
+}
+
+async fn monitor_notifications(
+    app_handle: AppHandle,
+    account: Account,
+    mut credentials: Credentials,
+    mut github: github::GitHub,
+) {
+    loop {
+        app_handle
+            .state::<Clients>()
+            .insert(&account.id, github.clone());
+        tauri::async_runtime::spawn(refresh_watched_repositories(
+            app_handle.clone(),
+            account.id.clone(),
+            github.clone(),
+        ));
+
+        let watching = watch_notifications(&app_handle, &account, &github);
+        // Expiring tokens are renewed ahead of time, the same way as rejected ones
+        let needs_new_token = match credentials.refresh_in() {
+            Some(refresh_in) => tokio::time::timeout(refresh_in, watching)
+                .await
+                .unwrap_or(true),
+            None => watching.await,
+        };
+
+        if !needs_new_token {
+            break;
+        }
+
+        match renew_session(&app_handle, &account, &credentials).await {
+            Ok((new_credentials, new_github)) => {
+                credentials = new_credentials;
+                github = new_github;
+            }
+            Err(error) => {
+                on_renewal_failed(&app_handle, &account, error);
+                break;
+            }
+        }
+    }
+}
+
+async fn refresh_watched_repositories(
+    app_handle: AppHandle,
+    account_id: String,
+    github: github::GitHub,
+) {
+    match github.list_watched_repositories().await {
+        Ok(repositories) => {
+            app_handle
+                .state::<WatchedRepositories>()
+                .set(&account_id, repositories);
+            refresh_tray_menu(&app_handle);
+        }
+        Err(error) => println!("Failed to list watched repositories: {}", error),
+    }
+}
+
+/// Stops notifications from a repository, `ignore` also drops the ones about the user's own involvement
+async fn unwatch_repository(
+    app_handle: AppHandle,
+    account_id: String,
+    full_name: String,
+    ignore: bool,
+) {
+    let Some(github) = app_handle.state::<Clients>().get(&account_id) else {
+        return;
+    };
+    let Some((owner, repo)) = full_name.split_once('/') else {
+        return;
+    };
+
+    let result = if ignore {
+        github
+            .set_repository_subscription(owner, repo, false, true)
+            .await
+            .map(|_| ())
+    } else {
+        github.delete_repository_subscription(owner, repo).await
+    };
+
+    match result {
+        Ok(()) => {
+            app_handle
+                .state::<WatchedRepositories>()
+                .remove_repository(&account_id, &full_name);
+            refresh_tray_menu(&app_handle);
+        }
+        Err(error) => println!("Failed to unwatch {}: {}", full_name, error),
+    }
+}
+
+/// Client using the network configuration and sharing the app's resolved URL cache
+async fn create_client(
+    app_handle: &AppHandle,
+    token: String,
+    host: Host,
+) -> Result<github::GitHub, GitHubError> {
+    let github = github::GitHub::new(token, host, &app_handle.state::<Config>().network).await?;
+
+    Ok(github.with_html_url_cache(app_handle.state::<Arc<HtmlUrlCache>>().inner().clone()))
+}
+
+/// Refreshes the account's token and creates a client using it. Failures like a network that isn't
+/// up yet are retried, so an error means the token can't be renewed or retrying won't help.
+async fn renew_session(
+    app_handle: &AppHandle,
+    account: &Account,
+    credentials: &Credentials,
+) -> Result<(Credentials, github::GitHub), AuthError> {
+    let refresh_token = credentials.refresh_token.as_ref().ok_or_else(|| {
+        AuthError::TokenExchange("the token was rejected and can't be refreshed".to_owned())
+    })?;
+    let network = &app_handle.state::<Config>().network;
+    let token_response = retry(app_handle, account, AuthError::is_transient, || {
+        auth::refresh_token(&account.host, network, refresh_token)
+    })
+    .await?;
+
+    let credentials = Credentials::from_token_response(&token_response);
+    // The old refresh token is used up, so the new one is kept for this session even if it can't be saved
+    if let Err(error) = app_handle
+        .state::<Accounts>()
+        .add(account.clone(), &credentials)
+    {
+        println!(
+            "Failed to save renewed credentials of {}: {:?}",
+            account.label(),
+            error
+        );
+    }
+    let github = retry(app_handle, account, GitHubError::is_transient, || {
+        create_client(
+            app_handle,
+            credentials.access_token.clone(),
+            account.host.clone(),
+        )
+    })
+    .await?;
+
+    Ok((credentials, github))
+}
+
+/// Runs `attempt` until it succeeds or fails with an error `should_retry` rejects, waiting with the
+/// stream's backoff in between and showing the retries in the tray
+async fn retry<T, E: fmt::Display, F: Future<Output = Result<T, E>>>(
+    app_handle: &AppHandle,
+    account: &Account,
+    should_retry: impl Fn(&E) -> bool,
+    mut attempt: impl FnMut() -> F,
+) -> Result<T, E> {
+    let mut failures = 0;
+
+    loop {
+        match attempt().await {
+            Err(error) if should_retry(&error) => {
+                failures += 1;
+                let retry_delay = github::backoff(failures);
+                println!(
+                    "Request for {} failed, retrying in {:?}: {}",
+                    account.label(),
+                    retry_delay,
+                    error
+                );
+                show_stream_health(
+                    app_handle,
+                    &account.id,
+                    StreamHealth::Retrying {
+                        failures,
+                        next_attempt: SystemTime::now() + retry_delay,
+                    },
+                );
+
+                tokio::time::sleep(retry_delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Shows notifications until the stream ends, returns `true` if it ended because the token was rejected
+async fn watch_notifications(
+    app_handle: &AppHandle,
+    account: &Account,
+    github: &github::GitHub,
+) -> bool {
+    let query = app_handle.state::<Config>().notifications.clone();
+    // A state saved for a different query would skip changes the new one should list
+    let state = app_handle
+        .state::<StreamStates>()
+        .get(&account.id)
+        .filter(|state| state.query == query)
+        .unwrap_or_else(|| github::StreamState::new(query));
+
+    let presenter = TrayPresenter {
+        app_handle,
+        account,
+        github,
+    };
+
+    NotificationMonitor::new(github.clone(), presenter)
+        .run(state)
+        .await
+}
+
+/// Records the account's stream health, the tray only changes if it did
+fn show_stream_health(app_handle: &AppHandle, account_id: &str, health: StreamHealth) {
+    if app_handle.state::<StreamHealths>().set(account_id, health) {
+        refresh_tray_menu(app_handle);
+    }
+}
+
+/// Shows an account's notifications in the tray and as system notifications
+struct TrayPresenter<'a> {
+    app_handle: &'a AppHandle,
+    account: &'a Account,
+    github: &'a github::GitHub,
+}
+
+impl NotificationPresenter for TrayPresenter<'_> {
+    fn show_unread_count(&self, count: usize) {
+        let unread_count = self
+            .app_handle
+            .state::<UnreadCounts>()
+            .set(&self.account.id, count);
+
+        update_tray_title(self.app_handle, unread_count);
+    }
+
+    fn show_health(&self, health: StreamHealth) {
+        show_stream_health(self.app_handle, &self.account.id, health);
+    }
+
+    async fn show_thread(&self, thread: &github::NotificationThread, url: tauri::Url) {
+        if let Err(error) = notifications::show_notification(
+            thread,
+            self.app_handle.clone(),
+            url.into(),
+            self.github,
+            &self.account.id,
+        )
+        .await
+        {
+            println!("Failed to show notification: {:?}", error);
+        }
+    }
+
+    fn show_summary(&self, title: &str, body: &str) {
+        if let Err(error) = self
+            .app_handle
+            .notification()
+            .builder()
+            .title(title)
+            .body(body)
+            .show()
+        {
+            println!("Failed to show notification summary: {:?}", error);
+        }
+    }
+
+    fn note_threads(&self, threads: &[github::NotificationThread]) {
+        self.app_handle
+            .state::<WatchedRepositories>()
+            .note_activity(
+                &self.account.id,
+                threads
+                    .iter()
+                    .rev()
+                    .map(|thread| thread.repository.full_name.as_str()),
+            );
+    }
+
+    fn save_stream_state(&self, state: github::StreamState) {
+        if let Err(error) = self
+            .app_handle
+            .state::<StreamStates>()
+            .set(&self.account.id, state)
+        {
+            println!("Failed to save stream state: {:?}", error);
+        }
+    }
 }
 
 fn create_tray_menu(
     app: &AppHandle,
-    is_authorized: bool,
+    accounts: &[Account],
 ) -> Result<tauri::menu::Menu<Wry>, Box<dyn std::error::Error>> {
     let mut menu_builder = MenuBuilder::new(app);
 
-    if !is_authorized {
-        menu_builder =
-            menu_builder.item(&MenuItemBuilder::with_id("auth", "Authenticate").build(app)?);
+    if accounts.is_empty() {
+        menu_builder = menu_builder
+            .item(&MenuItemBuilder::with_id("notifications", "Open notifications").build(app)?);
+    }
+
+    for account in accounts {
+        menu_builder = menu_builder.item(
+            &MenuItemBuilder::with_id(format!("account:{}", account.id), account.label())
+                .enabled(false)
+                .build(app)?,
+        );
+
+        if let Some(health_label) =
+            stream_health_label(&app.state::<StreamHealths>().get(&account.id))
+        {
+            menu_builder = menu_builder.item(
+                &MenuItemBuilder::with_id(format!("health:{}", account.id), health_label)
+                    .enabled(false)
+                    .build(app)?,
+            );
+        }
+
+        let watched_count = app.state::<WatchedRepositories>().get(&account.id).len();
+        if watched_count > 0 {
+            let watched_repositories = app
+                .state::<WatchedRepositories>()
+                .most_active(&account.id, MAX_TRAY_WATCHED_REPOSITORIES);
+            let mut submenu_builder = SubmenuBuilder::new(app, "Watched repositories");
+            for repository in &watched_repositories {
+                submenu_builder = submenu_builder.item(
+                    &SubmenuBuilder::new(app, &repository.full_name)
+                        .item(
+                            &MenuItemBuilder::with_id(
+                                format!("unwatch:{}:{}", account.id, repository.full_name),
+                                "Unwatch",
+                            )
+                            .build(app)?,
+                        )
+                        .item(
+                            &MenuItemBuilder::with_id(
+                                format!("ignore:{}:{}", account.id, repository.full_name),
+                                "Ignore",
+                            )
+                            .build(app)?,
+                        )
+                        .build()?,
+                );
+            }
+            if watched_count > watched_repositories.len() {
+                submenu_builder = submenu_builder.separator().item(
+                    &MenuItemBuilder::with_id(
+                        format!("watching:{}", account.id),
+                        format!("All {} watched repositories…", watched_count),
+                    )
+                    .build(app)?,
+                );
+            }
+
+            menu_builder = menu_builder.item(&submenu_builder.build()?);
+        }
+
+        menu_builder = menu_builder
+            .item(
+                &MenuItemBuilder::with_id(
+                    format!("notifications:{}", account.id),
+                    "Open notifications",
+                )
+                .build(app)?,
+            )
+            .item(
+                &MenuItemBuilder::with_id(
+                    format!("mark_all_as_read:{}", account.id),
+                    "Mark all as read",
+                )
+                .build(app)?,
+            )
+            .item(
+                &MenuItemBuilder::with_id(format!("sign_out:{}", account.id), "Sign out")
+                    .build(app)?,
+            )
+            .separator();
+    }
+
+    let auth_label = if accounts.is_empty() {
+        "Authenticate"
+    } else {
+        "Add account"
+    };
+
+    let credential_store_label = format!(
+        "Credentials: {}",
+        app.state::<Accounts>().credential_store_name()
+    );
+
+    let muted_threads = app.state::<MutedThreads>().list();
+    if !muted_threads.is_empty() {
+        let mut submenu_builder = SubmenuBuilder::new(app, "Muted threads");
+        for thread in muted_threads {
+            submenu_builder = submenu_builder.item(
+                &MenuItemBuilder::with_id(
+                    format!("unmute:{}:{}", thread.account_id, thread.thread_id),
+                    format!("Unmute {}: {}", thread.repository, thread.title),
+                )
+                .build(app)?,
+            );
+        }
+
+        menu_builder = menu_builder.item(&submenu_builder.build()?).separator();
     }
 
+    let hosts = app.state::<Config>().hosts();
+    menu_builder = sign_in_item(menu_builder, app, "auth", auth_label, &hosts)?;
+    menu_builder = sign_in_item(
+        menu_builder,
+        app,
+        "auth_device",
+        "Authenticate with device code",
+        &hosts,
+    )?;
+    menu_builder = sign_in_item(
+        menu_builder,
+        app,
+        "auth_pat",
+        "Use personal access token",
+        &hosts,
+    )?;
+
     let menu = menu_builder
-        .item(&MenuItemBuilder::with_id("notifications", "Open notifications").build(app)?)
+        .separator()
+        .item(
+            &MenuItemBuilder::with_id("credential_store", credential_store_label)
+                .enabled(false)
+                .build(app)?,
+        )
         .item(&PredefinedMenuItem::quit(app, Some("Quit"))?)
         .build()?;
 
     Ok(menu)
 }
 
-fn setup_tray(app: &AppHandle, is_authorized: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Sign in item for the only host, or a submenu to pick one of them. Ids end with the host's index.
+fn sign_in_item<'m>(
+    menu_builder: MenuBuilder<'m, Wry, AppHandle>,
+    app: &AppHandle,
+    id: &str,
+    label: &str,
+    hosts: &[Host],
+) -> tauri::Result<MenuBuilder<'m, Wry, AppHandle>> {
+    if hosts.len() == 1 {
+        return Ok(
+            menu_builder.item(&MenuItemBuilder::with_id(format!("{}:0", id), label).build(app)?)
+        );
+    }
+
+    let mut submenu_builder = SubmenuBuilder::new(app, label);
+    for (index, host) in hosts.iter().enumerate() {
+        submenu_builder = submenu_builder
+            .item(&MenuItemBuilder::with_id(format!("{}:{}", id, index), host.label()).build(app)?);
+    }
+
+    Ok(menu_builder.item(&submenu_builder.build()?))
+}
+
+fn stream_health_label(health: &StreamHealth) -> Option<String> {
+    match health {
+        StreamHealth::Healthy => None,
+        StreamHealth::Retrying { failures, .. } => Some(format!(
+            "Connection problems, retrying ({} failed attempts)",
+            failures
+        )),
+        StreamHealth::RateLimited { .. } => Some("Rate limited, polling paused".to_owned()),
+        StreamHealth::Failing(error) => Some(format!("Polling failed: {}", error)),
+    }
+}
+
+fn update_tray_title(app: &AppHandle, unread_count: usize) {
+    app.tray_by_id("tray")
+        .unwrap()
+        .set_title(if unread_count == 0 {
+            None
+        } else {
+            Some(unread_count.to_string())
+        })
+        .unwrap();
+}
+
+fn refresh_tray_menu(app: &AppHandle) {
+    let accounts = app.state::<Accounts>().list();
+
+    if let Some(tray) = app.tray_by_id("tray") {
+        tray.set_menu(create_tray_menu(app, &accounts).ok())
+            .unwrap();
+    }
+}
+
+fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     TrayIconBuilder::with_id("tray")
         .tooltip(app.package_info().name.clone())
         .icon(app.default_window_icon().unwrap().to_owned())
         .icon_as_template(true)
-        .menu(&create_tray_menu(app, is_authorized)?)
+        .menu(&create_tray_menu(app, &app.state::<Accounts>().list())?)
         .on_menu_event(move |app, event| match event.id().as_ref() {
-            "auth" => {
-                let app_handle = app.clone();
-
-                tauri::async_runtime::spawn(async move {
-                    let token_response = auth::get_token(&app_handle).await.unwrap();
-                    let token_entry = keyring::Entry::new("github-notifier", "user").unwrap();
-                    token_entry
-                        .set_password(token_response.access_token().secret())
-                        .unwrap();
-
-                    if let Some(tray) = app_handle.tray_by_id("tray") {
-                        tray.set_menu(create_tray_menu(&app_handle, true).ok())
-                            .unwrap();
-                    }
-
-                    start_monitoring_notifications(
-                        app_handle,
-                        token_response.access_token().secret().to_string(),
-                    );
-                });
-            }
             "notifications" => {
                 app.opener()
-                    .open_url("https://github.com/notifications", None::<&str>)
+                    .open_url(
+                        app.state::<Config>().host.web("notifications"),
+                        None::<&str>,
+                    )
                     .unwrap();
             }
-            _ => (),
+            id => {
+                if let Some(host) = id
+                    .strip_prefix("auth:")
+                    .and_then(|index| sign_in_host(app, index))
+                {
+                    tauri::async_runtime::spawn(sign_in(app.clone(), host));
+                } else if let Some(host) = id
+                    .strip_prefix("auth_device:")
+                    .and_then(|index| sign_in_host(app, index))
+                {
+                    tauri::async_runtime::spawn(sign_in_with_device_code(app.clone(), host));
+                } else if let Some(host) = id
+                    .strip_prefix("auth_pat:")
+                    .and_then(|index| sign_in_host(app, index))
+                {
+                    open_personal_access_token_window(app, host);
+                } else if let Some(account_id) = id.strip_prefix("notifications:") {
+                    if let Some(account) = find_account(app, account_id) {
+                        app.opener()
+                            .open_url(account.host.web("notifications"), None::<&str>)
+                            .unwrap();
+                    }
+                } else if let Some(account_id) = id.strip_prefix("watching:") {
+                    if let Some(account) = find_account(app, account_id) {
+                        let _ = app
+                            .opener()
+                            .open_url(account.host.web("watching"), None::<&str>);
+                    }
+                } else if let Some(account_id) = id.strip_prefix("mark_all_as_read:") {
+                    tauri::async_runtime::spawn(mark_all_as_read(
+                        app.clone(),
+                        account_id.to_owned(),
+                    ));
+                } else if let Some((account_id, thread_id)) = id
+                    .strip_prefix("unmute:")
+                    .and_then(|id| id.rsplit_once(':'))
+                {
+                    let app_handle = app.clone();
+                    let github = app.state::<Clients>().get(account_id);
+                    let thread = app.state::<MutedThreads>().find(account_id, thread_id);
+
+                    if let (Some(github), Some(thread)) = (github, thread) {
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(error) =
+                                muted_threads::unmute(&app_handle, &github, &thread).await
+                            {
+                                println!("Failed to unmute thread: {}", error);
+                            }
+                        });
+                    }
+                } else if let Some((account_id, full_name)) = id
+                    .strip_prefix("unwatch:")
+                    .and_then(|id| id.rsplit_once(':'))
+                {
+                    tauri::async_runtime::spawn(unwatch_repository(
+                        app.clone(),
+                        account_id.to_owned(),
+                        full_name.to_owned(),
+                        false,
+                    ));
+                } else if let Some((account_id, full_name)) = id
+                    .strip_prefix("ignore:")
+                    .and_then(|id| id.rsplit_once(':'))
+                {
+                    tauri::async_runtime::spawn(unwatch_repository(
+                        app.clone(),
+                        account_id.to_owned(),
+                        full_name.to_owned(),
+                        true,
+                    ));
+                } else if let Some(account_id) = id.strip_prefix("sign_out:") {
+                    if let Some(account) = find_account(app, account_id) {
+                        tauri::async_runtime::spawn(sign_out(app.clone(), account));
+                    }
+                }
+            }
         })
         .build(app)?;
 
     Ok(())
 }
+
+/// Host picked from the sign in menu by its index in `Config::hosts`
+fn sign_in_host(app: &AppHandle, index: &str) -> Option<Host> {
+    app.state::<Config>()
+        .hosts()
+        .into_iter()
+        .nth(index.parse().ok()?)
+}
+
+async fn sign_in(app_handle: AppHandle, host: Host) {
+    let result =
+        match auth::get_token(&app_handle, &host, &app_handle.state::<Config>().network).await {
+            Ok(token_response) => {
+                on_authenticated(
+                    app_handle.clone(),
+                    Credentials::from_token_response(&token_response),
+                    host,
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        };
+
+    if let Err(error) = result {
+        show_auth_error(&app_handle, &error);
+    }
+}
+
+async fn sign_in_with_device_code(app_handle: AppHandle, host: Host) {
+    let result = match auth::get_token_with_device_flow(
+        &app_handle,
+        &host,
+        &app_handle.state::<Config>().network,
+    )
+    .await
+    {
+        Ok(token_response) => {
+            on_authenticated(
+                app_handle.clone(),
+                Credentials::from_token_response(&token_response),
+                host,
+            )
+            .await
+        }
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = result {
+        show_auth_error(&app_handle, &error);
+    }
+}
+
+/// Host the personal access token window signs in to, it's only opened once for all hosts
+#[derive(Default)]
+struct PersonalAccessTokenHost(Mutex<Option<Host>>);
+
+fn open_personal_access_token_window(app: &AppHandle, host: Host) {
+    let title = format!("Use personal access token for {}", host.label());
+    *app.state::<PersonalAccessTokenHost>().0.lock().unwrap() = Some(host);
+
+    if let Some(window) = app.get_webview_window(PERSONAL_ACCESS_TOKEN_WINDOW) {
+        let _ = window.set_title(&title);
+        let _ = window.set_focus();
+    } else if let Err(error) = WebviewWindowBuilder::new(
+        app,
+        PERSONAL_ACCESS_TOKEN_WINDOW,
+        WebviewUrl::App("index.html".into()),
+    )
+    .title(title)
+    .inner_size(480.0, 360.0)
+    .resizable(false)
+    .center()
+    .build()
+    {
+        println!("Failed to open personal access token window: {:?}", error);
+        app.dialog()
+            .message(format!(
+                "The window to enter a personal access token couldn't be opened: {}",
+                error
+            ))
+            .title("Couldn't sign in to GitHub")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
+    }
+}
+
+async fn mark_all_as_read(app_handle: AppHandle, account_id: String) {
+    let Some(github) = app_handle.state::<Clients>().get(&account_id) else {
+        return;
+    };
+
+    match github.mark_all_as_read(Utc::now()).await {
+        Ok(status) => {
+            if status == MarkAsReadStatus::Processing {
+                println!("GitHub is marking notifications as read in the background");
+            }
+
+            let unread_count = app_handle.state::<UnreadCounts>().set(&account_id, 0);
+            update_tray_title(&app_handle, unread_count);
+        }
+        Err(error) => println!("Failed to mark notifications as read: {}", error),
+    }
+}
+
+fn find_account(app: &AppHandle, account_id: &str) -> Option<Account> {
+    app.state::<Accounts>()
+        .list()
+        .into_iter()
+        .find(|account| account.id == account_id)
+}
+
+#[tauri::command]
+async fn sign_in_with_personal_access_token(
+    app_handle: AppHandle,
+    token: String,
+) -> Result<(), String> {
+    let host = app_handle
+        .state::<PersonalAccessTokenHost>()
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| app_handle.state::<Config>().host.clone());
+    auth::validate_personal_access_token(&token, &host, &app_handle.state::<Config>().network)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    on_authenticated(
+        app_handle.clone(),
+        Credentials::new(token, CredentialsKind::PersonalAccessToken),
+        host,
+    )
+    .await
+    .map_err(|error| error.to_string())?;
+
+    if let Some(window) = app_handle.get_webview_window(PERSONAL_ACCESS_TOKEN_WINDOW) {
+        let _ = window.close();
+    }
+
+    Ok(())
+}
+
+fn show_auth_error(app_handle: &AppHandle, error: &AuthError) {
+    app_handle
+        .dialog()
+        .message(format!(
+            "{}\n\nYou can try again from the tray menu.",
+            error
+        ))
+        .title("Couldn't sign in to GitHub")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
+}
+
+async fn on_authenticated(
+    app_handle: AppHandle,
+    credentials: Credentials,
+    host: Host,
+) -> Result<(), AuthError> {
+    let github = create_client(&app_handle, credentials.access_token.clone(), host.clone()).await?;
+    let account = Account::new(github.user.login.clone(), host);
+    app_handle
+        .state::<Accounts>()
+        .add(account.clone(), &credentials)
+        .map_err(|error| AuthError::Storage(error.to_string()))?;
+
+    refresh_tray_menu(&app_handle);
+
+    let account_id = account.id.clone();
+    let task = tauri::async_runtime::spawn(monitor_notifications(
+        app_handle.clone(),
+        account,
+        credentials,
+        github,
+    ));
+    app_handle
+        .state::<MonitoringTasks>()
+        .insert(&account_id, task);
+
+    Ok(())
+}
+
+async fn sign_out(app_handle: AppHandle, account: Account) {
+    app_handle.state::<MonitoringTasks>().abort(&account.id);
+
+    // Personal access tokens weren't granted to the app, so only the user can revoke them
+    let credentials = app_handle
+        .state::<Accounts>()
+        .credentials(&account)
+        .filter(|credentials| credentials.kind == CredentialsKind::Grant);
+    if let Some(credentials) = credentials {
+        if let Err(error) = auth::revoke_grant(
+            &account.host,
+            &app_handle.state::<Config>().network,
+            &credentials.access_token,
+        )
+        .await
+        {
+            println!("Failed to revoke OAuth grant: {:?}", error);
+        }
+    }
+
+    forget_account(&app_handle, &account);
+}
+
+/// Signs the account out if its token was rejected, otherwise shows why its notifications stopped
+fn on_renewal_failed(app_handle: &AppHandle, account: &Account, error: AuthError) {
+    println!(
+        "Failed to renew the session of {}: {}",
+        account.label(),
+        error
+    );
+
+    match error {
+        AuthError::TokenExchange(_) | AuthError::GitHub(GitHubError::Unauthorized) => {
+            on_token_rejected(app_handle, account)
+        }
+        error => show_stream_health(
+            app_handle,
+            &account.id,
+            StreamHealth::Failing(error.to_string()),
+        ),
+    }
+}
+
+fn on_token_rejected(app_handle: &AppHandle, account: &Account) {
+    forget_account(app_handle, account);
+
+    if let Err(error) = app_handle
+        .notification()
+        .builder()
+        .title("Signed out of GitHub")
+        .body(format!(
+            "Access for {} has been revoked or has expired. Sign in again from the tray menu.",
+            account.label()
+        ))
+        .show()
+    {
+        println!("Failed to show sign out notification: {:?}", error);
+    }
+}
+
+fn forget_account(app_handle: &AppHandle, account: &Account) {
+    if let Err(error) = app_handle.state::<Accounts>().remove(&account.id) {
+        println!("Failed to forget {}: {:?}", account.label(), error);
+    }
+
+    app_handle.state::<StreamHealths>().remove(&account.id);
+    app_handle.state::<Clients>().remove(&account.id);
+    app_handle
+        .state::<WatchedRepositories>()
+        .remove(&account.id);
+    if let Err(error) = app_handle
+        .state::<MutedThreads>()
+        .remove_account(&account.id)
+    {
+        println!("Failed to save muted threads: {:?}", error);
+    }
+    if let Err(error) = app_handle.state::<StreamStates>().remove(&account.id) {
+        println!("Failed to save stream states: {:?}", error);
+    }
+    let unread_count = app_handle.state::<UnreadCounts>().remove(&account.id);
+    update_tray_title(app_handle, unread_count);
+    refresh_tray_menu(app_handle);
+}
@@ -0,0 +1,217 @@
+use std::future::Future;
+
+use futures::{Stream, StreamExt};
+use url::Url;
+
+use crate::{
+    config::Host,
+    github::{
+        GitHub, GitHubError, NotificationThread, NotificationsUpdate, StreamHealth, StreamState,
+    },
+};
+
+/// From this many new notifications on a single summary is shown instead of one per thread
+const SUMMARY_THRESHOLD: usize = 5;
+
+/// Actions available on a thread without any app state, unlike muting or unwatching
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadAction {
+    MarkAsRead,
+    MarkAsDone,
+    MarkRepositoryAsRead,
+}
+
+impl ThreadAction {
+    /// Action of a Windows toast button
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "read" => Some(Self::MarkAsRead),
+            "done" => Some(Self::MarkAsDone),
+            "read_repository" => Some(Self::MarkRepositoryAsRead),
+            _ => None,
+        }
+    }
+
+    /// Action of a macOS notification dropdown entry
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "Mark as read" => Some(Self::MarkAsRead),
+            "Mark as done" => Some(Self::MarkAsDone),
+            "Mark repository as read" => Some(Self::MarkRepositoryAsRead),
+            _ => None,
+        }
+    }
+}
+
+/// Where notifications come from, `GitHub` outside of tests
+pub trait NotificationSource {
+    fn host(&self) -> &Host;
+
+    /// Login of the signed in user, shown in summaries
+    fn login(&self) -> &str;
+
+    fn health(&self) -> StreamHealth;
+
+    fn stream_state(&self) -> StreamState;
+
+    /// Polls notifications continuing from `state`, see `GitHub::notifications_stream_with_state`
+    fn stream(&self, state: StreamState) -> impl Stream<Item = NotificationsUpdate> + Send + '_;
+
+    /// Web pages of the threads in order, `None` for those that couldn't be resolved
+    fn resolve_urls(
+        &self,
+        threads: &[NotificationThread],
+    ) -> impl Future<Output = Vec<Option<Url>>> + Send;
+
+    fn perform(
+        &self,
+        thread: &NotificationThread,
+        action: ThreadAction,
+    ) -> impl Future<Output = Result<(), GitHubError>> + Send;
+}
+
+impl NotificationSource for GitHub {
+    fn host(&self) -> &Host {
+        &self.host
+    }
+
+    fn login(&self) -> &str {
+        &self.user.login
+    }
+
+    fn health(&self) -> StreamHealth {
+        GitHub::health(self)
+    }
+
+    fn stream_state(&self) -> StreamState {
+        GitHub::stream_state(self)
+    }
+
+    fn stream(&self, state: StreamState) -> impl Stream<Item = NotificationsUpdate> + Send + '_ {
+        self.notifications_stream_with_state(state)
+    }
+
+    async fn resolve_urls(&self, threads: &[NotificationThread]) -> Vec<Option<Url>> {
+        let mut urls = Vec::with_capacity(threads.len());
+        for thread in threads {
+            urls.push(self.generate_github_url(thread, self.user.id).await);
+        }
+        // Written once per poll rather than once per resolved URL
+        self.flush_html_url_cache();
+
+        urls
+    }
+
+    async fn perform(
+        &self,
+        thread: &NotificationThread,
+        action: ThreadAction,
+    ) -> Result<(), GitHubError> {
+        match action {
+            ThreadAction::MarkAsRead => self.mark_thread_as_read(&thread.id).await,
+            ThreadAction::MarkAsDone => self.mark_thread_as_done(&thread.id).await,
+            ThreadAction::MarkRepositoryAsRead => self
+                .mark_repository_as_read(
+                    &thread.repository.owner.login,
+                    &thread.repository.name,
+                    chrono::Utc::now(),
+                )
+                .await
+                .map(|_| ()),
+        }
+    }
+}
+
+/// Performs an action picked on a notification, failures are only logged as there's nowhere to show them
+pub async fn perform_action<S: NotificationSource>(
+    source: &S,
+    thread: &NotificationThread,
+    action: ThreadAction,
+) {
+    if let Err(error) = source.perform(thread, action).await {
+        println!(
+            "Failed to perform {:?} on thread {}: {:?}",
+            action, thread.id, error
+        );
+    }
+}
+
+/// Where notifications end up, the tray and system notifications outside of tests
+pub trait NotificationPresenter {
+    /// Number of unread notifications of the account
+    fn show_unread_count(&self, count: usize);
+
+    /// Called after every poll, whether or not the health changed
+    fn show_health(&self, health: StreamHealth);
+
+    fn show_thread(&self, thread: &NotificationThread, url: Url)
+        -> impl Future<Output = ()> + Send;
+
+    fn show_summary(&self, title: &str, body: &str);
+
+    /// Every unread thread after a poll that changed them, whether they're shown one by one or not
+    fn note_threads(&self, _threads: &[NotificationThread]) {}
+
+    /// Called after every poll once its threads were shown, so the stream can be continued after a restart
+    fn save_stream_state(&self, state: StreamState);
+}
+
+/// Turns notification updates from a source into what the presenter shows
+pub struct NotificationMonitor<S, P> {
+    pub source: S,
+    pub presenter: P,
+}
+
+impl<S: NotificationSource, P: NotificationPresenter> NotificationMonitor<S, P> {
+    pub fn new(source: S, presenter: P) -> Self {
+        Self { source, presenter }
+    }
+
+    /// Shows notifications until the stream ends, returns `true` if it ended because the token was rejected
+    pub async fn run(&self, state: StreamState) -> bool {
+        if state.unread_count > 0 {
+            self.presenter.show_unread_count(state.unread_count);
+        }
+
+        let notification_stream = self.source.stream(state);
+        futures::pin_mut!(notification_stream);
+
+        while let Some(update) = notification_stream.next().await {
+            self.presenter.show_health(self.source.health());
+
+            match update {
+                NotificationsUpdate::Updated(threads) => {
+                    self.presenter.show_unread_count(threads.len());
+                    self.presenter.note_threads(&threads);
+                    self.show(threads).await;
+                }
+                NotificationsUpdate::Failed(GitHubError::Unauthorized) => return true,
+                NotificationsUpdate::Unchanged | NotificationsUpdate::Failed(_) => {}
+            }
+
+            // Only once the threads were shown, so they're shown again if the app quits before
+            self.presenter.save_stream_state(self.source.stream_state());
+        }
+
+        false
+    }
+
+    async fn show(&self, threads: Vec<NotificationThread>) {
+        if threads.len() < SUMMARY_THRESHOLD {
+            let urls = self.source.resolve_urls(&threads).await;
+            for (thread, url) in threads.iter().zip(urls) {
+                let url = url.unwrap_or_else(|| self.source.host().web("notifications"));
+
+                self.presenter.show_thread(thread, url).await;
+            }
+        } else {
+            let body = format!(
+                "{} has {} new notifications",
+                self.source.login(),
+                threads.len()
+            );
+
+            self.presenter.show_summary("New notifications!", &body);
+        }
+    }
+}
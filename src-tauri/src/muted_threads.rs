@@ -0,0 +1,158 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    github::{GitHub, GitHubError, NotificationThread},
+    json_file,
+};
+
+const MUTED_THREADS_FILE_NAME: &str = "muted_threads.json";
+
+/// Thread muted from a notification, remembered so muting can be undone from the tray
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MutedThread {
+    pub account_id: String,
+    pub thread_id: String,
+    pub title: String,
+    pub repository: String,
+    /// Unmuting restores the subscription if the user had one before muting
+    pub was_subscribed: bool,
+}
+
+pub struct MutedThreads {
+    path: Option<PathBuf>,
+    threads: Mutex<Vec<MutedThread>>,
+}
+
+impl MutedThreads {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(MUTED_THREADS_FILE_NAME));
+        let threads = path
+            .as_deref()
+            .and_then(json_file::load)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            threads: Mutex::new(threads),
+        }
+    }
+
+    pub fn list(&self) -> Vec<MutedThread> {
+        self.threads.lock().unwrap().clone()
+    }
+
+    pub fn find(&self, account_id: &str, thread_id: &str) -> Option<MutedThread> {
+        self.threads
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|thread| thread.account_id == account_id && thread.thread_id == thread_id)
+            .cloned()
+    }
+
+    pub fn add(&self, thread: MutedThread) -> Result<(), Box<dyn std::error::Error>> {
+        {
+            let mut threads = self.threads.lock().unwrap();
+            threads.retain(|existing| {
+                existing.account_id != thread.account_id || existing.thread_id != thread.thread_id
+            });
+            threads.push(thread);
+        }
+
+        self.save()
+    }
+
+    pub fn remove(
+        &self,
+        account_id: &str,
+        thread_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.threads
+            .lock()
+            .unwrap()
+            .retain(|thread| thread.account_id != account_id || thread.thread_id != thread_id);
+
+        self.save()
+    }
+
+    /// Forgets every thread muted by the account, used when signing out
+    pub fn remove_account(&self, account_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.threads
+            .lock()
+            .unwrap()
+            .retain(|thread| thread.account_id != account_id);
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Err("App data directory is not available".into());
+        };
+
+        json_file::save(path, &*self.threads.lock().unwrap())
+    }
+}
+
+/// Ignores the thread, so unlike unsubscribing GitHub won't resubscribe the user on the next mention
+pub async fn mute(
+    app_handle: &AppHandle,
+    github: &GitHub,
+    account_id: &str,
+    thread: &NotificationThread,
+) -> Result<(), GitHubError> {
+    let was_subscribed = match github.get_thread_subscription(&thread.id).await {
+        Ok(subscription) => subscription.subscribed,
+        // Notified only because of involvement in the thread, without a subscription
+        Err(GitHubError::NotFound) => false,
+        Err(error) => return Err(error),
+    };
+
+    github.set_thread_subscription(&thread.id, true).await?;
+    github.mark_thread_as_read(&thread.id).await?;
+
+    let muted_thread = MutedThread {
+        account_id: account_id.to_owned(),
+        thread_id: thread.id.clone(),
+        title: thread.subject.title.clone(),
+        repository: thread.repository.full_name.clone(),
+        was_subscribed,
+    };
+    if let Err(error) = app_handle.state::<MutedThreads>().add(muted_thread) {
+        println!("Failed to save muted thread: {:?}", error);
+    }
+    crate::refresh_tray_menu(app_handle);
+
+    Ok(())
+}
+
+pub async fn unmute(
+    app_handle: &AppHandle,
+    github: &GitHub,
+    thread: &MutedThread,
+) -> Result<(), GitHubError> {
+    if thread.was_subscribed {
+        github
+            .set_thread_subscription(&thread.thread_id, false)
+            .await?;
+    } else {
+        github.delete_thread_subscription(&thread.thread_id).await?;
+    }
+
+    if let Err(error) = app_handle
+        .state::<MutedThreads>()
+        .remove(&thread.account_id, &thread.thread_id)
+    {
+        println!("Failed to save muted threads: {:?}", error);
+    }
+    crate::refresh_tray_menu(app_handle);
+
+    Ok(())
+}
@@ -3,7 +3,11 @@ use std::path::MAIN_SEPARATOR as SEP;
 #[cfg(windows)]
 use std::sync::{Arc, Mutex};
 
-use crate::{github::NotificationThread, utils};
+use crate::{
+    github::NotificationThread,
+    monitor::{self, ThreadAction},
+    muted_threads, utils,
+};
 use tauri::AppHandle;
 use tauri_plugin_opener::OpenerExt;
 
@@ -13,6 +17,7 @@ pub async fn show_notification(
     app_handle: AppHandle,
     url: String,
     github: &crate::github::GitHub,
+    account_id: &str,
 ) -> anyhow::Result<()> {
     let exe = tauri::utils::platform::current_exe()?;
     let exe_dir = exe.parent().expect("failed to get exe directory");
@@ -27,46 +32,71 @@ pub async fn show_notification(
     };
 
     let icon = Arc::new(Mutex::new(
-        utils::download_icon(thread.repository.owner.avatar_url.as_str())
-            .await
-            .unwrap(),
+        download_avatar(thread, github, &app_handle).await,
     ));
 
-    tauri_winrt_notification::Toast::new(app_id)
+    let mut toast = tauri_winrt_notification::Toast::new(app_id)
         .title(thread.subject.title.as_str())
         .text1(thread.repository.full_name.as_str())
-        .icon(
-            icon.lock().unwrap().path(),
+        .text2(&summary(thread, github));
+    if let Some(icon) = icon.lock().unwrap().as_ref() {
+        toast = toast.icon(
+            icon.path(),
             tauri_winrt_notification::IconCrop::Circular,
             thread.subject.title.as_str(),
-        )
+        );
+    }
+
+    toast
+        .add_button("Mark as read", "read")
         .add_button("Mark as done", "done")
-        .add_button("Unsubscribe", "unsubscribe")
+        .add_button("Mark repository as read", "read_repository")
+        .add_button("Mute thread", "mute")
+        .add_button("Unwatch repository", "unwatch")
         .on_activated({
             let icon = Arc::clone(&icon);
-            let thread_id = thread.id.clone();
+            let thread = thread.clone();
+            let account_id = account_id.to_owned();
+            let full_name = thread.repository.full_name.clone();
             let github = github.clone();
 
             move |action| {
-                icon.lock().unwrap().clone().cleanup().unwrap();
+                if let Some(icon) = icon.lock().unwrap().clone() {
+                    icon.cleanup().unwrap();
+                }
+
+                if let Some(action) = action.as_deref().and_then(ThreadAction::from_id) {
+                    tauri::async_runtime::spawn({
+                        let thread = thread.clone();
+                        let github = github.clone();
+                        async move { monitor::perform_action(&github, &thread, action).await }
+                    });
+
+                    return Ok(());
+                }
 
                 match action.as_deref() {
-                    Some("done") => {
-                        tauri::async_runtime::spawn({
-                            let thread_id = thread_id.clone();
-                            let github = github.clone();
-                            async move {
-                                github.mark_thread_as_done(&thread_id).await.unwrap();
-                            }
-                        });
+                    Some("unwatch") => {
+                        tauri::async_runtime::spawn(crate::unwatch_repository(
+                            app_handle.clone(),
+                            account_id.clone(),
+                            full_name.clone(),
+                            false,
+                        ));
                     }
-                    Some("unsubscribe") => {
+                    Some("mute") => {
                         tauri::async_runtime::spawn({
-                            let thread_id = thread_id.clone();
+                            let app_handle = app_handle.clone();
+                            let account_id = account_id.clone();
+                            let thread = thread.clone();
                             let github = github.clone();
                             async move {
-                                github.delete_thread_subscription(&thread_id).await.unwrap();
-                                github.mark_thread_as_done(&thread_id).await.unwrap();
+                                if let Err(error) =
+                                    muted_threads::mute(&app_handle, &github, &account_id, &thread)
+                                        .await
+                                {
+                                    println!("Failed to mute thread: {:?}", error);
+                                }
                             }
                         });
                     }
@@ -82,7 +112,9 @@ pub async fn show_notification(
             let icon = Arc::clone(&icon);
 
             move |_| {
-                icon.lock().unwrap().clone().cleanup().unwrap();
+                if let Some(icon) = icon.lock().unwrap().clone() {
+                    icon.cleanup().unwrap();
+                }
                 Ok(())
             }
         })
@@ -97,29 +129,60 @@ pub async fn show_notification(
     thread: &NotificationThread,
     app_handle: AppHandle,
     url: String,
-    _github: &crate::github::GitHub,
+    github: &crate::github::GitHub,
+    account_id: &str,
 ) -> anyhow::Result<()> {
     let app_id = if tauri::is_dev() {
         "com.apple.Terminal"
     } else {
         app_handle.config().identifier.as_str()
     };
-    let icon = utils::download_icon(thread.repository.owner.avatar_url.as_str()).await?;
+    let icon = download_avatar(thread, github, &app_handle).await;
 
     mac_notification_sys::set_application(app_id).unwrap_or_default();
-    let response = mac_notification_sys::Notification::default()
+    let summary = summary(thread, github);
+    let mut notification = mac_notification_sys::Notification::default();
+    notification
         .title(thread.subject.title.as_str())
-        .message(thread.repository.full_name.as_str())
-        .content_image(icon.path().to_str().unwrap())
-        .send()
-        .unwrap();
+        .subtitle(thread.repository.full_name.as_str())
+        .message(&summary);
+    notification.main_button(mac_notification_sys::MainButton::DropdownActions(
+        "Actions",
+        &[
+            "Mark as read",
+            "Mark as done",
+            "Mark repository as read",
+            "Mute thread",
+            "Unwatch repository",
+            "Ignore repository",
+        ],
+    ));
+    if let Some(icon) = &icon {
+        notification.content_image(icon.path().to_str().unwrap());
+    }
+    let response = notification.send().unwrap();
 
     match response {
         mac_notification_sys::NotificationResponse::ActionButton(action_name) => {
-            if action_name == "Mark as done" {
-                println!("Clicked on Mark as done")
-            } else if action_name == "Unsubscribe" {
-                println!("Clicked on Unsubscribe")
+            if let Some(action) = ThreadAction::from_label(&action_name) {
+                monitor::perform_action(github, thread, action).await;
+                return Ok(());
+            }
+
+            match action_name.as_str() {
+                "Unwatch repository" | "Ignore repository" => {
+                    crate::unwatch_repository(
+                        app_handle.clone(),
+                        account_id.to_owned(),
+                        thread.repository.full_name.clone(),
+                        action_name == "Ignore repository",
+                    )
+                    .await
+                }
+                "Mute thread" => {
+                    muted_threads::mute(&app_handle, github, account_id, thread).await?
+                }
+                _ => {}
             }
         }
         mac_notification_sys::NotificationResponse::Click => {
@@ -130,3 +193,45 @@ pub async fn show_notification(
 
     Ok(())
 }
+
+/// e.g. "Review requested · Pull request · Draft · by monalisa · octocat"
+#[cfg(any(windows, target_os = "macos"))]
+fn summary(thread: &NotificationThread, github: &crate::github::GitHub) -> String {
+    let mut parts = vec![
+        thread.reason.label().to_owned(),
+        thread.subject.r#type.label().to_owned(),
+    ];
+    if let Some(details) = &thread.details {
+        parts.push(details.state.label().to_owned());
+        if let Some(author) = &details.author {
+            parts.push(format!("by {}", author));
+        }
+    }
+    parts.push(github.user.login.clone());
+
+    parts.join(" · ")
+}
+
+/// Notifications are shown without the avatar when it fails to download or the rate limit is low
+#[cfg(any(windows, target_os = "macos"))]
+async fn download_avatar(
+    thread: &NotificationThread,
+    github: &crate::github::GitHub,
+    app_handle: &AppHandle,
+) -> Option<temp_file::TempFile> {
+    use tauri::Manager;
+
+    if github.is_rate_limit_low() {
+        return None;
+    }
+
+    let client = app_handle
+        .state::<utils::IconClient>()
+        .get(&app_handle.state::<crate::config::Config>().network)
+        .inspect_err(|error| println!("Failed to create avatar client: {}", error))
+        .ok()?;
+    utils::download_icon(thread.repository.owner.avatar_url.as_str(), &client)
+        .await
+        .inspect_err(|error| println!("Failed to download avatar: {:?}", error))
+        .ok()
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{github::StreamState, json_file};
+
+const STREAM_STATES_FILE_NAME: &str = "stream_states.json";
+
+/// Notifications stream state per account id, saved after every poll
+pub struct StreamStates {
+    path: Option<PathBuf>,
+    states: Mutex<HashMap<String, StreamState>>,
+}
+
+impl StreamStates {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join(STREAM_STATES_FILE_NAME));
+        let states = path
+            .as_deref()
+            .and_then(json_file::load)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            states: Mutex::new(states),
+        }
+    }
+
+    pub fn get(&self, account_id: &str) -> Option<StreamState> {
+        self.states.lock().unwrap().get(account_id).cloned()
+    }
+
+    pub fn set(
+        &self,
+        account_id: &str,
+        state: StreamState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.states
+            .lock()
+            .unwrap()
+            .insert(account_id.to_owned(), state);
+
+        self.save()
+    }
+
+    pub fn remove(&self, account_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.states.lock().unwrap().remove(account_id);
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Err("App data directory is not available".into());
+        };
+
+        json_file::save(path, &*self.states.lock().unwrap())
+    }
+}
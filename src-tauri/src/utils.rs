@@ -1,8 +1,30 @@
+use std::sync::Mutex;
+
 use tauri_plugin_http::reqwest;
 use temp_file::TempFile;
 
-pub async fn download_icon(url: &str) -> anyhow::Result<TempFile> {
-    let icon_content = reqwest::get(url).await?.bytes().await?;
+use crate::config::{NetworkConfig, NetworkError};
+
+/// Client for icon downloads, built from the network config on first use and shared after that
+#[derive(Default)]
+pub struct IconClient(Mutex<Option<reqwest::Client>>);
+
+impl IconClient {
+    pub fn get(&self, network: &NetworkConfig) -> Result<reqwest::Client, NetworkError> {
+        let mut client = self.0.lock().unwrap();
+        if let Some(client) = &*client {
+            return Ok(client.clone());
+        }
+
+        let new_client = network.client()?;
+        *client = Some(new_client.clone());
+
+        Ok(new_client)
+    }
+}
+
+pub async fn download_icon(url: &str, client: &reqwest::Client) -> anyhow::Result<TempFile> {
+    let icon_content = client.get(url).send().await?.bytes().await?;
     let icon_file = TempFile::with_suffix(".png")?.with_contents(&icon_content)?;
 
     Ok(icon_file)
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use github_notifier_lib::credential_store::{CredentialStore, EncryptedFileStore};
+
+/// Fresh directory per test, so tests running in parallel don't share a store
+fn data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "github-notifier-credential-store-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    dir
+}
+
+#[test]
+fn encrypted_file_store_round_trips_secrets() {
+    let dir = data_dir("round-trip");
+    let store = EncryptedFileStore::open_with_passphrase(&dir, None).unwrap();
+
+    assert_eq!(store.get("account").unwrap(), None);
+
+    store.set("account", "gho_secret").unwrap();
+    assert_eq!(store.get("account").unwrap().as_deref(), Some("gho_secret"));
+    // Only ciphertext ends up on disk
+    let content = std::fs::read_to_string(dir.join("credentials.json")).unwrap();
+    assert!(!content.contains("gho_secret"));
+
+    store.delete("account").unwrap();
+    assert_eq!(store.get("account").unwrap(), None);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encrypted_file_store_reopens_with_the_same_key() {
+    let dir = data_dir("reopen");
+    EncryptedFileStore::open_with_passphrase(&dir, None)
+        .unwrap()
+        .set("account", "gho_secret")
+        .unwrap();
+
+    let store = EncryptedFileStore::open_with_passphrase(&dir, None).unwrap();
+
+    assert_eq!(store.get("account").unwrap().as_deref(), Some("gho_secret"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encrypted_file_store_rejects_wrong_passphrase() {
+    let dir = data_dir("passphrase");
+    EncryptedFileStore::open_with_passphrase(&dir, Some("correct horse"))
+        .unwrap()
+        .set("account", "gho_secret")
+        .unwrap();
+
+    let store = EncryptedFileStore::open_with_passphrase(&dir, Some("battery staple")).unwrap();
+
+    assert!(store.get("account").is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn encrypted_file_store_reports_corrupted_files() {
+    let dir = data_dir("corrupted");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("credentials.key"), b"too short").unwrap();
+
+    assert!(EncryptedFileStore::open_with_passphrase(&dir, None).is_err());
+
+    std::fs::write(dir.join("credentials.json"), "not json").unwrap();
+
+    assert!(EncryptedFileStore::open_with_passphrase(&dir, Some("passphrase")).is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
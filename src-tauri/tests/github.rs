@@ -0,0 +1,937 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use github_notifier_lib::{
+    config::{Host, NetworkConfig, NetworkError, ProxyConfig},
+    github::{
+        GitHub, GitHubError, MarkAsReadStatus, NotificationQuery, NotificationThread,
+        NotificationsUpdate, Reason, StreamHealth, StreamState, SubjectDetails, SubjectState,
+        SubjectType,
+    },
+    html_url_cache::HtmlUrlCache,
+};
+use serde_json::json;
+use url::Url;
+use wiremock::{
+    matchers::{body_json, body_partial_json, header, method, path, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+const LAST_MODIFIED: &str = "Thu, 25 Oct 2012 15:16:27 GMT";
+
+/// `header` splits values on commas, which HTTP dates contain
+fn if_modified_since(value: &'static str) -> impl Fn(&Request) -> bool {
+    move |request| {
+        request
+            .headers
+            .get("If-Modified-Since")
+            .is_some_and(|header| header == value)
+    }
+}
+
+fn host(server: &MockServer) -> Host {
+    Host {
+        web_url: Url::parse("https://github.com").unwrap(),
+        api_url: Url::parse(&server.uri()).unwrap(),
+        client_id: None,
+    }
+}
+
+async fn client(server: &MockServer) -> GitHub {
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .and(header("Authorization", "Bearer token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 1,
+            "login": "octocat",
+        })))
+        .mount(server)
+        .await;
+
+    GitHub::new("token".to_owned(), host(server), &NetworkConfig::default())
+        .await
+        .unwrap()
+}
+
+fn notification_thread(
+    server: &MockServer,
+    latest_comment_url: Option<String>,
+) -> serde_json::Value {
+    json!({
+        "id": "1",
+        "repository": {
+            "id": 1296269,
+            "name": "Hello-World",
+            "full_name": "octocat/Hello-World",
+            "description": "This your first repo!",
+            "owner": {
+                "login": "octocat",
+                "avatar_url": "https://github.com/images/error/octocat_happy.gif",
+            },
+        },
+        "subject": {
+            "title": "Greetings",
+            "url": format!("{}/repos/octocat/Hello-World/issues/123", server.uri()),
+            "latest_comment_url": latest_comment_url,
+            "type": "Issue",
+        },
+        "reason": "subscribed",
+        "unread": true,
+        "updated_at": "2014-11-07T22:01:45Z",
+        "last_read_at": "2014-11-07T22:01:45Z",
+        "url": format!("{}/notifications/threads/1", server.uri()),
+        "subscription_url": format!("{}/notifications/threads/1/subscription", server.uri()),
+    })
+}
+
+fn thread(server: &MockServer, latest_comment_url: Option<String>) -> NotificationThread {
+    serde_json::from_value(notification_thread(server, latest_comment_url)).unwrap()
+}
+
+fn updated(update: Option<NotificationsUpdate>) -> Vec<NotificationThread> {
+    match update {
+        Some(NotificationsUpdate::Updated(threads)) => threads,
+        other => panic!("expected updated notifications, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn stream_yields_fetched_notifications() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([notification_thread(&server, None)])),
+        )
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    let threads = updated(stream.next().await);
+
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].id, "1");
+    assert_eq!(threads[0].subject.title, "Greetings");
+    assert_eq!(threads[0].repository.full_name, "octocat/Hello-World");
+    assert_eq!(threads[0].reason, Reason::Subscribed);
+    assert_eq!(threads[0].subject.r#type, SubjectType::Issue);
+    assert_eq!(
+        threads[0].updated_at,
+        "2014-11-07T22:01:45Z".parse::<DateTime<Utc>>().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn stream_combines_notification_pages() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .and(query_param("page", "2"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([notification_thread(&server, None)])),
+        )
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .and(query_param("per_page", "100"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header(
+                    "Link",
+                    format!(
+                        r#"<{0}/notifications?per_page=100&page=2>; rel="next", <{0}/notifications?per_page=100&page=2>; rel="last""#,
+                        server.uri()
+                    ),
+                )
+                .set_body_json(json!([notification_thread(&server, None)])),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    let threads = updated(stream.next().await);
+
+    assert_eq!(threads.len(), 2);
+}
+
+#[tokio::test]
+async fn stream_applies_notification_query() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/notifications"))
+        .and(query_param("all", "true"))
+        .and(query_param("participating", "true"))
+        .and(query_param("since", "2014-11-07T22:01:45Z"))
+        .and(query_param("before", "2014-11-08T22:01:45Z"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let query = NotificationQuery::new()
+        .all(true)
+        .participating(true)
+        .since("2014-11-07T22:01:45Z".parse().unwrap())
+        .before("2014-11-08T22:01:45Z".parse().unwrap())
+        .repository("octocat", "Hello-World");
+    let stream = github.notifications_stream(query);
+    futures::pin_mut!(stream);
+
+    updated(stream.next().await);
+}
+
+#[tokio::test]
+async fn stream_reports_not_modified_notifications_as_none() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .and(if_modified_since(LAST_MODIFIED))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("Last-Modified", LAST_MODIFIED)
+                .insert_header("X-Poll-Interval", "1")
+                .set_body_json(json!([])),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+
+    updated(stream.next().await);
+    assert!(matches!(
+        stream.next().await,
+        Some(NotificationsUpdate::Unchanged)
+    ));
+}
+
+#[tokio::test]
+async fn stream_continues_from_saved_state() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .and(if_modified_since(LAST_MODIFIED))
+        .respond_with(ResponseTemplate::new(304).insert_header("X-Poll-Interval", "30"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let query = NotificationQuery::new().participating(true);
+    let saved = StreamState {
+        last_modified: Some(LAST_MODIFIED.to_owned()),
+        poll_interval: Some(1),
+        last_poll: Some(Utc::now()),
+        unread_count: 3,
+        ..StreamState::new(query.clone())
+    };
+    let stream = github.notifications_stream_with_state(saved);
+    futures::pin_mut!(stream);
+
+    assert!(matches!(
+        stream.next().await,
+        Some(NotificationsUpdate::Unchanged)
+    ));
+
+    let state = github.stream_state();
+    assert_eq!(state.query, query);
+    assert_eq!(state.last_modified.as_deref(), Some(LAST_MODIFIED));
+    assert_eq!(state.poll_interval, Some(30));
+    assert!(state.last_poll.is_some());
+    // Not modified, so the saved count is still current
+    assert_eq!(state.unread_count, 3);
+}
+
+#[tokio::test]
+async fn stream_ignores_invalid_saved_last_modified() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .and(|request: &Request| !request.headers.contains_key("If-Modified-Since"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let saved = StreamState {
+        last_modified: Some("Thu, 25 Oct 2012\n15:16:27 GMT".to_owned()),
+        ..StreamState::new(NotificationQuery::new())
+    };
+    let stream = github.notifications_stream_with_state(saved);
+    futures::pin_mut!(stream);
+
+    updated(stream.next().await);
+}
+
+#[tokio::test]
+async fn stream_follows_poll_interval_header() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-Poll-Interval", "1")
+                .set_body_json(json!([])),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    updated(stream.next().await);
+
+    // Default interval is a minute, so the second poll only happens this quickly after the header was applied
+    updated(
+        tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("second poll should follow X-Poll-Interval"),
+    );
+}
+
+#[tokio::test]
+async fn stream_reports_rejected_token() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    assert!(matches!(
+        stream.next().await,
+        Some(NotificationsUpdate::Failed(GitHubError::Unauthorized))
+    ));
+}
+
+#[tokio::test]
+async fn generate_github_url_prefers_latest_comment() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+    let thread = thread(
+        &server,
+        Some(format!(
+            "{}/repos/octocat/Hello-World/issues/comments/1",
+            server.uri()
+        )),
+    );
+
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/issues/comments/1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "html_url": "https://github.com/octocat/Hello-World/issues/123#issuecomment-1",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let url = github.generate_github_url(&thread, 1).await.unwrap();
+
+    assert_eq!(url.path(), "/octocat/Hello-World/issues/123");
+    assert_eq!(url.fragment(), Some("issuecomment-1"));
+    assert_eq!(
+        url.query_pairs()
+            .find(|(key, _)| key == "notification_referrer_id")
+            .map(|(_, value)| value.into_owned()),
+        Some(GitHub::generate_notification_referrer_id("1", 1))
+    );
+}
+
+#[tokio::test]
+async fn generate_github_url_falls_back_to_subject() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+    let thread = thread(&server, None);
+
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/issues/123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "html_url": "https://github.com/octocat/Hello-World/issues/123",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let url = github.generate_github_url(&thread, 1).await.unwrap();
+
+    assert_eq!(url.path(), "/octocat/Hello-World/issues/123");
+}
+
+#[tokio::test]
+async fn generate_github_url_skips_referrer_id_for_other_hosts() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+    let thread = thread(&server, None);
+
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/issues/123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "html_url": "https://github.example.com/octocat/Hello-World/issues/123",
+        })))
+        .mount(&server)
+        .await;
+
+    let url = github.generate_github_url(&thread, 1).await.unwrap();
+
+    assert_eq!(url.query(), None);
+}
+
+#[tokio::test]
+async fn mark_thread_as_done_deletes_thread() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("DELETE"))
+        .and(path("/notifications/threads/1"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    github.mark_thread_as_done("1").await.unwrap();
+}
+
+#[tokio::test]
+async fn delete_thread_subscription_deletes_subscription() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("DELETE"))
+        .and(path("/notifications/threads/1/subscription"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    github.delete_thread_subscription("1").await.unwrap();
+}
+
+#[tokio::test]
+async fn generate_github_url_is_skipped_when_rate_limit_is_low() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+    let thread = thread(&server, None);
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-RateLimit-Remaining", "10")
+                .insert_header("X-RateLimit-Reset", "4102444800")
+                .set_body_json(json!([])),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/issues/123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "html_url": "https://github.com/octocat/Hello-World/issues/123",
+        })))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    updated(stream.next().await);
+
+    assert!(github.is_rate_limit_low());
+    assert_eq!(github.generate_github_url(&thread, 1).await, None);
+}
+
+#[tokio::test]
+async fn stream_waits_for_retry_after() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("X-Poll-Interval", "1")
+                .set_body_json(json!([])),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "2"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    updated(stream.next().await);
+    assert!(matches!(
+        stream.next().await,
+        Some(NotificationsUpdate::Failed(GitHubError::RateLimited {
+            reset: Some(_)
+        }))
+    ));
+
+    // The regular interval is a second, the extra wait comes from Retry-After
+    let started = std::time::Instant::now();
+    updated(stream.next().await);
+    assert!(started.elapsed() >= Duration::from_millis(1500));
+}
+
+#[tokio::test]
+async fn requests_report_typed_errors() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("DELETE"))
+        .and(path("/notifications/threads/1"))
+        .respond_with(ResponseTemplate::new(403).insert_header(
+            "X-GitHub-SSO",
+            "required; url=https://github.com/orgs/octo-org/sso",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/notifications/threads/2"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/notifications/threads/3"))
+        .respond_with(ResponseTemplate::new(502).set_body_string("Bad Gateway"))
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        github.mark_thread_as_done("1").await,
+        Err(GitHubError::Forbidden { sso_url: Some(url) }) if url == "https://github.com/orgs/octo-org/sso"
+    ));
+    assert!(matches!(
+        github.mark_thread_as_done("2").await,
+        Err(GitHubError::NotFound)
+    ));
+    assert!(matches!(
+        github.mark_thread_as_done("3").await,
+        Err(GitHubError::Server { status, message }) if status == 502 && message == "Bad Gateway"
+    ));
+}
+
+#[tokio::test]
+async fn client_sends_requests_through_configured_proxy() {
+    let proxy = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/user"))
+        .and(header("Proxy-Authorization", "Basic dXNlcjpzZWNyZXQ="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 1,
+            "login": "octocat",
+        })))
+        .expect(1)
+        .mount(&proxy)
+        .await;
+
+    let network = NetworkConfig {
+        proxy: Some(ProxyConfig {
+            url: Url::parse(&proxy.uri()).unwrap(),
+            username: Some("user".to_owned()),
+            password: Some("secret".to_owned()),
+            no_proxy: None,
+        }),
+        ..NetworkConfig::default()
+    };
+    // Only reachable through the proxy
+    let host = Host {
+        web_url: Url::parse("http://github.invalid").unwrap(),
+        api_url: Url::parse("http://api.github.invalid").unwrap(),
+        client_id: None,
+    };
+
+    let github = GitHub::new("token".to_owned(), host, &network)
+        .await
+        .unwrap();
+
+    assert_eq!(github.user.login, "octocat");
+}
+
+#[tokio::test]
+async fn client_reports_unreadable_ca_certificates() {
+    let server = MockServer::start().await;
+    let network = NetworkConfig {
+        ca_certificates: vec!["does-not-exist.pem".into()],
+        ..NetworkConfig::default()
+    };
+
+    assert!(matches!(
+        GitHub::new("token".to_owned(), host(&server), &network).await,
+        Err(GitHubError::Configuration(NetworkError::Certificate { .. }))
+    ));
+}
+
+#[tokio::test]
+async fn stream_recovers_from_transient_errors() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+
+    assert!(matches!(
+        stream.next().await,
+        Some(NotificationsUpdate::Failed(GitHubError::Server { .. }))
+    ));
+    assert!(matches!(
+        github.health(),
+        StreamHealth::Retrying { failures: 1, .. }
+    ));
+
+    // The first retry happens after the base delay instead of the regular minute
+    updated(
+        tokio::time::timeout(Duration::from_secs(10), stream.next())
+            .await
+            .expect("failed poll should be retried with a backoff"),
+    );
+    assert_eq!(github.health(), StreamHealth::Healthy);
+}
+
+#[test]
+fn unknown_reasons_and_subject_types_are_kept() {
+    let reason: Reason = serde_json::from_value(json!("review_requested")).unwrap();
+    assert_eq!(reason, Reason::ReviewRequested);
+
+    let reason: Reason = serde_json::from_value(json!("something_new")).unwrap();
+    assert_eq!(reason, Reason::Unknown("something_new".to_owned()));
+
+    let subject_type: SubjectType = serde_json::from_value(json!("PullRequest")).unwrap();
+    assert_eq!(subject_type, SubjectType::PullRequest);
+
+    let subject_type: SubjectType = serde_json::from_value(json!("Gist")).unwrap();
+    assert_eq!(subject_type, SubjectType::Unknown("Gist".to_owned()));
+}
+
+#[tokio::test]
+async fn mark_thread_as_read_patches_thread() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("PATCH"))
+        .and(path("/notifications/threads/1"))
+        .respond_with(ResponseTemplate::new(205))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    github.mark_thread_as_read("1").await.unwrap();
+}
+
+#[tokio::test]
+async fn mark_all_as_read_sends_last_read_at() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("PUT"))
+        .and(path("/notifications"))
+        .and(body_json(json!({
+            "last_read_at": "2014-11-07T22:01:45Z",
+            "read": true,
+        })))
+        .respond_with(ResponseTemplate::new(205))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = github
+        .mark_all_as_read("2014-11-07T22:01:45Z".parse().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(status, MarkAsReadStatus::Done);
+}
+
+#[tokio::test]
+async fn mark_repository_as_read_reports_background_processing() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("PUT"))
+        .and(path("/repos/octocat/Hello-World/notifications"))
+        .respond_with(ResponseTemplate::new(202).set_body_json(json!({
+            "message": "Unread notifications couldn't be marked in a single request. Notifications are being marked as read in the background.",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let status = github
+        .mark_repository_as_read("octocat", "Hello-World", Utc::now())
+        .await
+        .unwrap();
+
+    assert_eq!(status, MarkAsReadStatus::Processing);
+}
+
+#[tokio::test]
+async fn thread_subscription_can_be_ignored() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications/threads/1/subscription"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subscribed": true,
+            "ignored": false,
+            "reason": null,
+            "created_at": "2012-10-06T21:34:12Z",
+            "url": format!("{}/notifications/threads/1/subscription", server.uri()),
+            "thread_url": format!("{}/notifications/threads/1", server.uri()),
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/notifications/threads/1/subscription"))
+        .and(body_json(json!({ "ignored": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subscribed": false,
+            "ignored": true,
+            "reason": null,
+            "created_at": "2012-10-06T21:34:12Z",
+            "url": format!("{}/notifications/threads/1/subscription", server.uri()),
+            "thread_url": format!("{}/notifications/threads/1", server.uri()),
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let subscription = github.get_thread_subscription("1").await.unwrap();
+    assert!(subscription.subscribed);
+    assert!(!subscription.ignored);
+
+    let subscription = github.set_thread_subscription("1", true).await.unwrap();
+    assert!(subscription.ignored);
+}
+
+#[tokio::test]
+async fn repository_subscriptions_can_be_listed_and_changed() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+    let repository = notification_thread(&server, None)["repository"].clone();
+
+    Mock::given(method("GET"))
+        .and(path("/user/subscriptions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([repository])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/repos/octocat/Hello-World/subscription"))
+        .and(body_json(json!({ "subscribed": false, "ignored": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "subscribed": false,
+            "ignored": true,
+            "reason": null,
+            "created_at": "2012-10-06T21:34:12Z",
+            "url": format!("{}/repos/octocat/Hello-World/subscription", server.uri()),
+            "repository_url": format!("{}/repos/octocat/Hello-World", server.uri()),
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/repos/octocat/Hello-World/subscription"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let repositories = github.list_watched_repositories().await.unwrap();
+    assert_eq!(repositories.len(), 1);
+    assert_eq!(repositories[0].full_name, "octocat/Hello-World");
+
+    let subscription = github
+        .set_repository_subscription("octocat", "Hello-World", false, true)
+        .await
+        .unwrap();
+    assert!(subscription.ignored);
+
+    github
+        .delete_repository_subscription("octocat", "Hello-World")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn stream_attaches_subject_details() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("GET"))
+        .and(path("/notifications"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([notification_thread(&server, None)])),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/graphql"))
+        .and(body_partial_json(json!({
+            "variables": { "owner0": "octocat", "name0": "Hello-World", "number0": 123 },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": {
+                "s0": {
+                    "issue": {
+                        "state": "CLOSED",
+                        "author": { "login": "monalisa" },
+                        "labels": { "nodes": [{ "name": "bug" }] },
+                        "comments": { "totalCount": 3 },
+                    },
+                },
+            },
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let stream = github.notifications_stream(NotificationQuery::new());
+    futures::pin_mut!(stream);
+    let threads = updated(stream.next().await);
+
+    assert_eq!(
+        threads[0].details,
+        Some(SubjectDetails {
+            state: SubjectState::Closed,
+            author: Some("monalisa".to_owned()),
+            labels: vec!["bug".to_owned()],
+            review_decision: None,
+            comment_count: 3,
+        })
+    );
+}
+
+#[tokio::test]
+async fn fetch_subject_details_reports_rejected_queries() {
+    let server = MockServer::start().await;
+    let github = client(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path("/graphql"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "errors": [{ "message": "Your token has not been granted the required scopes" }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert!(matches!(
+        github.fetch_subject_details(&[thread(&server, None)]).await,
+        Err(GitHubError::GraphQL(messages))
+            if messages == ["Your token has not been granted the required scopes"]
+    ));
+}
+
+#[tokio::test]
+async fn generate_github_url_reuses_cached_urls() {
+    let server = MockServer::start().await;
+    let github = client(&server)
+        .await
+        .with_html_url_cache(Arc::new(HtmlUrlCache::load(None)));
+    let mut thread = thread(&server, None);
+
+    Mock::given(method("GET"))
+        .and(path("/repos/octocat/Hello-World/issues/123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "html_url": "https://github.com/octocat/Hello-World/issues/123",
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let first = github.generate_github_url(&thread, 1).await;
+    let second = github.generate_github_url(&thread, 1).await;
+    assert_eq!(first, second);
+
+    // An update may point somewhere else, e.g. a new comment, so it is resolved again
+    thread.updated_at = "2014-11-08T22:01:45Z".parse().unwrap();
+    github.generate_github_url(&thread, 1).await.unwrap();
+}
+
+#[test]
+fn html_url_cache_is_only_written_on_flush() {
+    let path = std::env::temp_dir().join(format!(
+        "github-notifier-html-url-cache-{}.json",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let updated_at: DateTime<Utc> = "2014-11-07T22:01:45Z".parse().unwrap();
+
+    let cache = HtmlUrlCache::load(Some(path.clone()));
+    cache.insert(
+        "https://api.github.com/repos/octocat/Hello-World/issues/123",
+        updated_at,
+        "https://github.com/octocat/Hello-World/issues/123",
+    );
+    assert!(!path.exists());
+
+    cache.flush();
+    let reloaded = HtmlUrlCache::load(Some(path.clone()));
+    assert_eq!(
+        reloaded
+            .get(
+                "https://api.github.com/repos/octocat/Hello-World/issues/123",
+                &updated_at
+            )
+            .as_deref(),
+        Some("https://github.com/octocat/Hello-World/issues/123")
+    );
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,376 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use futures::{stream, Stream};
+use github_notifier_lib::{
+    config::Host,
+    github::{
+        GitHubError, NotificationQuery, NotificationThread, NotificationsUpdate, StreamHealth,
+        StreamState,
+    },
+    monitor::{
+        perform_action, NotificationMonitor, NotificationPresenter, NotificationSource,
+        ThreadAction,
+    },
+};
+use serde_json::json;
+use url::Url;
+
+/// Yields the given updates once, then ends like a stream whose task was cancelled
+#[derive(Default)]
+struct FakeSource {
+    host: Host,
+    updates: Mutex<Vec<NotificationsUpdate>>,
+    /// Resolved URLs by thread id, other threads can't be resolved
+    urls: HashMap<String, Url>,
+    /// Thread ids and actions performed on them, in order
+    performed: Mutex<Vec<(String, ThreadAction)>>,
+    /// Makes every action fail once performed
+    failing: bool,
+}
+
+impl FakeSource {
+    fn new(updates: Vec<NotificationsUpdate>) -> Self {
+        Self {
+            host: Host::github_com(None),
+            updates: Mutex::new(updates),
+            ..Default::default()
+        }
+    }
+}
+
+impl NotificationSource for FakeSource {
+    fn host(&self) -> &Host {
+        &self.host
+    }
+
+    fn login(&self) -> &str {
+        "octocat"
+    }
+
+    fn health(&self) -> StreamHealth {
+        StreamHealth::Healthy
+    }
+
+    fn stream_state(&self) -> StreamState {
+        StreamState::new(NotificationQuery::new())
+    }
+
+    fn stream(&self, _state: StreamState) -> impl Stream<Item = NotificationsUpdate> + Send + '_ {
+        stream::iter(std::mem::take(&mut *self.updates.lock().unwrap()))
+    }
+
+    async fn resolve_urls(&self, threads: &[NotificationThread]) -> Vec<Option<Url>> {
+        threads
+            .iter()
+            .map(|thread| self.urls.get(&thread.id).cloned())
+            .collect()
+    }
+
+    async fn perform(
+        &self,
+        thread: &NotificationThread,
+        action: ThreadAction,
+    ) -> Result<(), GitHubError> {
+        self.performed
+            .lock()
+            .unwrap()
+            .push((thread.id.clone(), action));
+
+        if self.failing {
+            Err(GitHubError::NotFound)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Shown {
+    UnreadCount(usize),
+    Health(StreamHealth),
+    Thread { id: String, url: String },
+    Summary { title: String, body: String },
+    SavedState,
+}
+
+/// Records everything it's asked to show, in order
+#[derive(Default)]
+struct FakePresenter {
+    shown: Mutex<Vec<Shown>>,
+}
+
+impl FakePresenter {
+    fn shown(&self) -> Vec<Shown> {
+        self.shown.lock().unwrap().clone()
+    }
+
+    /// Only what the user gets to see, without health and saved states
+    fn visible(&self) -> Vec<Shown> {
+        self.shown()
+            .into_iter()
+            .filter(|shown| !matches!(shown, Shown::Health(_) | Shown::SavedState))
+            .collect()
+    }
+}
+
+impl NotificationPresenter for FakePresenter {
+    fn show_unread_count(&self, count: usize) {
+        self.shown.lock().unwrap().push(Shown::UnreadCount(count));
+    }
+
+    fn show_health(&self, health: StreamHealth) {
+        self.shown.lock().unwrap().push(Shown::Health(health));
+    }
+
+    async fn show_thread(&self, thread: &NotificationThread, url: Url) {
+        self.shown.lock().unwrap().push(Shown::Thread {
+            id: thread.id.clone(),
+            url: url.to_string(),
+        });
+    }
+
+    fn show_summary(&self, title: &str, body: &str) {
+        self.shown.lock().unwrap().push(Shown::Summary {
+            title: title.to_owned(),
+            body: body.to_owned(),
+        });
+    }
+
+    fn save_stream_state(&self, _state: StreamState) {
+        self.shown.lock().unwrap().push(Shown::SavedState);
+    }
+}
+
+fn thread(id: &str, subject_type: &str, reason: &str, updated_at: &str) -> NotificationThread {
+    serde_json::from_value(json!({
+        "id": id,
+        "repository": {
+            "id": 1296269,
+            "name": "Hello-World",
+            "full_name": "octocat/Hello-World",
+            "description": null,
+            "owner": {
+                "login": "octocat",
+                "avatar_url": "https://github.com/images/error/octocat_happy.gif",
+            },
+        },
+        "subject": {
+            "title": "Greetings",
+            "url": null,
+            "latest_comment_url": null,
+            "type": subject_type,
+        },
+        "reason": reason,
+        "unread": true,
+        "updated_at": updated_at,
+        "last_read_at": null,
+        "url": format!("https://api.github.com/notifications/threads/{}", id),
+        "subscription_url": format!("https://api.github.com/notifications/threads/{}/subscription", id),
+    }))
+    .unwrap()
+}
+
+fn monitor(updates: Vec<NotificationsUpdate>) -> NotificationMonitor<FakeSource, FakePresenter> {
+    NotificationMonitor::new(FakeSource::new(updates), FakePresenter::default())
+}
+
+#[tokio::test]
+async fn few_notifications_are_shown_one_by_one() {
+    let mut monitor = monitor(vec![NotificationsUpdate::Updated(vec![
+        thread("2", "Issue", "subscribed", "2014-11-07T22:05:00Z"),
+        thread("1", "Issue", "subscribed", "2014-11-07T22:01:45Z"),
+    ])]);
+    monitor.source.urls = HashMap::from([
+        (
+            "1".to_owned(),
+            Url::parse("https://github.com/octocat/Hello-World/issues/1").unwrap(),
+        ),
+        (
+            "2".to_owned(),
+            Url::parse("https://github.com/octocat/Hello-World/issues/2").unwrap(),
+        ),
+    ]);
+
+    assert!(!monitor.run(StreamState::default()).await);
+    assert_eq!(
+        monitor.presenter.visible(),
+        vec![
+            Shown::UnreadCount(2),
+            Shown::Thread {
+                id: "2".to_owned(),
+                url: "https://github.com/octocat/Hello-World/issues/2".to_owned(),
+            },
+            Shown::Thread {
+                id: "1".to_owned(),
+                url: "https://github.com/octocat/Hello-World/issues/1".to_owned(),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn many_notifications_are_summarized() {
+    let monitor = monitor(vec![NotificationsUpdate::Updated(vec![
+        thread("1", "Issue", "subscribed", "2014-11-07T22:01:45Z"),
+        thread(
+            "2",
+            "PullRequest",
+            "review_requested",
+            "2014-11-07T22:01:45Z",
+        ),
+        thread("3", "Issue", "mention", "2014-11-07T22:01:45Z"),
+        thread("4", "Release", "subscribed", "2014-11-07T22:01:45Z"),
+        thread("5", "Commit", "author", "2014-11-07T22:01:45Z"),
+    ])]);
+
+    monitor.run(StreamState::default()).await;
+
+    assert_eq!(
+        monitor.presenter.visible(),
+        vec![
+            Shown::UnreadCount(5),
+            Shown::Summary {
+                title: "New notifications!".to_owned(),
+                body: "octocat has 5 new notifications".to_owned(),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn unresolved_urls_fall_back_to_the_notifications_page() {
+    let monitor = monitor(vec![NotificationsUpdate::Updated(vec![
+        thread("1", "CheckSuite", "ci_activity", "2014-11-07T22:01:00Z"),
+        thread("2", "Discussion", "subscribed", "2014-11-07T22:02:00Z"),
+        thread("3", "Issue", "subscribed", "2014-11-07T22:03:00Z"),
+    ])]);
+
+    monitor.run(StreamState::default()).await;
+
+    let urls: Vec<_> = monitor
+        .presenter
+        .visible()
+        .into_iter()
+        .filter_map(|shown| match shown {
+            Shown::Thread { url, .. } => Some(url),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        urls,
+        vec![
+            "https://github.com/notifications",
+            "https://github.com/notifications",
+            "https://github.com/notifications",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn rejected_token_stops_monitoring() {
+    let monitor = monitor(vec![
+        NotificationsUpdate::Failed(GitHubError::Unauthorized),
+        NotificationsUpdate::Updated(vec![thread(
+            "1",
+            "Issue",
+            "subscribed",
+            "2014-11-07T22:01:45Z",
+        )]),
+    ]);
+
+    assert!(monitor.run(StreamState::default()).await);
+    assert!(monitor.presenter.visible().is_empty());
+}
+
+#[tokio::test]
+async fn saved_unread_count_is_kept_until_notifications_change() {
+    let monitor = monitor(vec![
+        NotificationsUpdate::Unchanged,
+        NotificationsUpdate::Failed(GitHubError::NotFound),
+    ]);
+    let state = StreamState {
+        unread_count: 3,
+        ..StreamState::default()
+    };
+
+    assert!(!monitor.run(state).await);
+    // Health and state are reported after every poll, even ones without changes
+    assert_eq!(
+        monitor.presenter.shown(),
+        vec![
+            Shown::UnreadCount(3),
+            Shown::Health(StreamHealth::Healthy),
+            Shown::SavedState,
+            Shown::Health(StreamHealth::Healthy),
+            Shown::SavedState,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stream_state_is_saved_after_threads_are_shown() {
+    let monitor = monitor(vec![NotificationsUpdate::Updated(vec![thread(
+        "1",
+        "Issue",
+        "subscribed",
+        "2014-11-07T22:01:45Z",
+    )])]);
+
+    monitor.run(StreamState::default()).await;
+
+    assert_eq!(
+        monitor.presenter.shown(),
+        vec![
+            Shown::Health(StreamHealth::Healthy),
+            Shown::UnreadCount(1),
+            Shown::Thread {
+                id: "1".to_owned(),
+                url: "https://github.com/notifications".to_owned(),
+            },
+            Shown::SavedState,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn notification_buttons_perform_thread_actions() {
+    let source = FakeSource::new(Vec::new());
+    let thread = thread("1", "Issue", "subscribed", "2014-11-07T22:01:45Z");
+
+    for button in ["read", "done", "read_repository", "mute", "unwatch"] {
+        if let Some(action) = ThreadAction::from_id(button) {
+            perform_action(&source, &thread, action).await;
+        }
+    }
+    for label in ["Mark as read", "Mute thread", "Ignore repository"] {
+        if let Some(action) = ThreadAction::from_label(label) {
+            perform_action(&source, &thread, action).await;
+        }
+    }
+
+    assert_eq!(
+        *source.performed.lock().unwrap(),
+        vec![
+            ("1".to_owned(), ThreadAction::MarkAsRead),
+            ("1".to_owned(), ThreadAction::MarkAsDone),
+            ("1".to_owned(), ThreadAction::MarkRepositoryAsRead),
+            ("1".to_owned(), ThreadAction::MarkAsRead),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn failed_thread_actions_are_only_logged() {
+    let source = FakeSource {
+        failing: true,
+        ..FakeSource::new(Vec::new())
+    };
+    let thread = thread("1", "Issue", "subscribed", "2014-11-07T22:01:45Z");
+
+    perform_action(&source, &thread, ThreadAction::MarkAsDone).await;
+
+    assert_eq!(
+        *source.performed.lock().unwrap(),
+        vec![("1".to_owned(), ThreadAction::MarkAsDone)]
+    );
+}
@@ -1,51 +1,55 @@
 import { useState } from "react";
-import reactLogo from "./assets/react.svg";
 import { invoke } from "@tauri-apps/api/core";
 import "./App.css";
 
 function App() {
-  const [greetMsg, setGreetMsg] = useState("");
-  const [name, setName] = useState("");
-
-  async function greet() {
-    // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
-    setGreetMsg(await invoke("greet", { name }));
+  const [token, setToken] = useState("");
+  const [error, setError] = useState("");
+  const [isSubmitting, setIsSubmitting] = useState(false);
+
+  async function signIn() {
+    setIsSubmitting(true);
+    setError("");
+
+    try {
+      await invoke("sign_in_with_personal_access_token", { token });
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setIsSubmitting(false);
+    }
   }
 
   return (
     <div className="container">
-      <h1>Welcome to Tauri!</h1>
-
-      <div className="row">
-        <a href="https://vitejs.dev" target="_blank">
-          <img src="/vite.svg" className="logo vite" alt="Vite logo" />
-        </a>
-        <a href="https://tauri.app" target="_blank">
-          <img src="/tauri.svg" className="logo tauri" alt="Tauri logo" />
-        </a>
-        <a href="https://reactjs.org" target="_blank">
-          <img src={reactLogo} className="logo react" alt="React logo" />
-        </a>
-      </div>
-
-      <p>Click on the Tauri, Vite, and React logos to learn more.</p>
+      <h1>Use personal access token</h1>
+
+      <p>
+        Paste a classic personal access token with the{" "}
+        <code>notifications</code> scope. Fine-grained tokens can't access
+        notifications.
+      </p>
 
       <form
         className="row"
         onSubmit={(e) => {
           e.preventDefault();
-          greet();
+          signIn();
         }}
       >
         <input
-          id="greet-input"
-          onChange={(e) => setName(e.currentTarget.value)}
-          placeholder="Enter a name..."
+          id="token-input"
+          type="password"
+          autoFocus
+          onChange={(e) => setToken(e.currentTarget.value.trim())}
+          placeholder="ghp_..."
         />
-        <button type="submit">Greet</button>
+        <button type="submit" disabled={!token || isSubmitting}>
+          Sign in
+        </button>
       </form>
 
-      <p>{greetMsg}</p>
+      <p className="error">{error}</p>
     </div>
   );
 }